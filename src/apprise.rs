@@ -0,0 +1,289 @@
+//! Apprise-style notification URLs, mapped onto the built-in backends.
+//!
+//! `matrix://` goes to the Matrix backend; `tgram://`, `discord://`, `ntfy://` and `json://` are
+//! posted by [`Webhook`] with the count and the newest unread illust. Other schemes are rejected
+//! with an error so the caller can skip them.
+
+use anyhow::{bail, Context, Result};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use illust_notify::Tracker;
+use serde_json::json;
+
+use crate::callback::{Event, Payload};
+use crate::matrix::MatrixConfig;
+use crate::notifier::Notifier;
+
+pub enum Backend {
+    /// `matrixs://TOKEN@host/!room:server` or `matrix://` for plain HTTP.
+    Matrix(MatrixConfig),
+    Webhook(Webhook),
+}
+
+enum Service {
+    /// `tgram://BOT_TOKEN/CHAT_ID[/CHAT_ID...]`
+    Telegram { token: String, chats: Vec<String> },
+    /// `discord://WEBHOOK_ID/WEBHOOK_TOKEN`
+    Discord { id: String, token: String },
+    /// `ntfys://[USER:PASS@]host/TOPIC`, `ntfy://` for plain HTTP or `ntfy://TOPIC` for ntfy.sh.
+    Ntfy { url: String, auth: Option<Auth> },
+    /// `jsons://[USER:PASS@]host[:port]/path`, posted in Apprise's JSON format.
+    Json { url: String, auth: Option<Auth> },
+}
+
+struct Auth {
+    user: String,
+    password: Option<String>,
+}
+
+pub struct Webhook {
+    name: &'static str,
+    service: Service,
+    client: reqwest::Client,
+}
+
+/// Percent-encodes a path segment or query value such as a room id.
+pub fn percent_encode(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            r.push(b as char);
+        } else {
+            r.push_str(&format!("%{:02X}", b));
+        }
+    }
+    r
+}
+
+pub fn percent_decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' && i + 2 < b.len() {
+            let hex = std::str::from_utf8(&b[i + 1..i + 3]).ok();
+            if let Some(v) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(b[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Splits `[USER[:PASS]@]rest`.
+fn split_auth(rest: &str) -> (Option<Auth>, &str) {
+    match rest.split_once('@') {
+        Some((auth, rest)) => {
+            let (user, password) = match auth.split_once(':') {
+                Some((u, p)) => (u, Some(percent_decode(p))),
+                None => (auth, None),
+            };
+            let user = percent_decode(user);
+            (Some(Auth { user, password }), rest)
+        }
+        None => (None, rest),
+    }
+}
+
+fn segments(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_decode)
+        .collect()
+}
+
+fn service(scheme: &str, rest: &str) -> Result<Service> {
+    let rest = rest.split('?').next().unwrap_or_default();
+    let http = if scheme.ends_with('s') {
+        "https"
+    } else {
+        "http"
+    };
+    Ok(match scheme {
+        "tgram" => {
+            let mut s = segments(rest).into_iter();
+            let token = s.next().context("missing bot token")?;
+            let chats: Vec<_> = s.collect();
+            if chats.is_empty() {
+                bail!("missing chat id");
+            }
+            Service::Telegram { token, chats }
+        }
+        "discord" => match segments(rest)[..] {
+            [ref id, ref token, ..] => Service::Discord {
+                id: id.clone(),
+                token: token.clone(),
+            },
+            _ => bail!("missing webhook id or token"),
+        },
+        "ntfy" | "ntfys" => {
+            let (auth, rest) = split_auth(rest);
+            let url = match rest.trim_end_matches('/').split_once('/') {
+                Some((host, topic)) => format!("{}://{}/{}", http, host, topic),
+                None if rest.is_empty() => bail!("missing topic"),
+                None => format!("https://ntfy.sh/{}", rest),
+            };
+            Service::Ntfy { url, auth }
+        }
+        "json" | "jsons" => {
+            let (auth, rest) = split_auth(rest);
+            if rest.is_empty() {
+                bail!("missing host");
+            }
+            Service::Json {
+                url: format!("{}://{}", http, rest),
+                auth,
+            }
+        }
+        _ => bail!("unsupported scheme {:?}", scheme),
+    })
+}
+
+pub fn parse(url: &str) -> Result<Backend> {
+    let (scheme, rest) = url.split_once("://").context("missing scheme")?;
+    let name = match scheme {
+        "matrix" | "matrixs" => {
+            return Ok(Backend::Matrix(MatrixConfig::from_url(
+                scheme == "matrixs",
+                rest,
+            )?))
+        }
+        "tgram" => "telegram",
+        "discord" => "discord",
+        "ntfy" | "ntfys" => "ntfy",
+        "json" | "jsons" => "json",
+        _ => bail!("unsupported scheme {:?}", scheme),
+    };
+    Ok(Backend::Webhook(Webhook {
+        name,
+        service: service(scheme, rest)?,
+        client: reqwest::Client::new(),
+    }))
+}
+
+impl Webhook {
+    /// The count, then the newest unread illust with its link.
+    fn text(app: &Tracker) -> String {
+        let mut text = app.summary();
+        if let Some(illust) = app.newest_shown() {
+            let m = app.messages();
+            let by = m.by(&illust.title, &illust.user.name);
+            text += &format!(
+                "\n{} https://www.pixiv.net/artworks/{}",
+                m.newest(&by),
+                illust.id
+            );
+        }
+        text
+    }
+
+    async fn send(&self, app: &Tracker) -> Result<()> {
+        let text = Self::text(app);
+        let title = app.messages().count(&app.dist_text());
+        let requests = match &self.service {
+            Service::Telegram { token, chats } => chats
+                .iter()
+                .map(|chat| {
+                    let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
+                    self.client
+                        .post(&url)
+                        .json(&json!({ "chat_id": chat, "text": &text }))
+                })
+                .collect(),
+            Service::Discord { id, token } => {
+                let url = format!("https://discord.com/api/webhooks/{}/{}", id, token);
+                vec![self.client.post(&url).json(&json!({ "content": text }))]
+            }
+            Service::Ntfy { url, auth } => {
+                let r = self.client.post(url).header("Title", &title).body(text);
+                vec![with_auth(r, auth)]
+            }
+            Service::Json { url, auth } => {
+                let body = json!({
+                    "version": "1.0",
+                    "title": title,
+                    "message": text,
+                    "type": "info",
+                });
+                vec![with_auth(self.client.post(url).json(&body), auth)]
+            }
+        };
+        for r in requests {
+            r.send().await?.error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+fn with_auth(r: reqwest::RequestBuilder, auth: &Option<Auth>) -> reqwest::RequestBuilder {
+    match auth {
+        Some(a) => r.basic_auth(&a.user, a.password.as_ref()),
+        None => r,
+    }
+}
+
+impl Notifier for Webhook {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn accepts(&self, event: Event) -> bool {
+        event == Event::CountChanged
+    }
+
+    fn notify<'a>(
+        &'a mut self,
+        app: &'a Tracker,
+        _: &'a Payload<'_>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        // A hung server would hold up the main loop.
+        async move { app.timed(self.name, self.send(app)).await }.boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(backend: Backend) -> String {
+        match backend {
+            Backend::Webhook(Webhook {
+                service: Service::Ntfy { url, .. } | Service::Json { url, .. },
+                ..
+            }) => url,
+            _ => panic!("not an ntfy or json url"),
+        }
+    }
+
+    #[test]
+    fn parses_schemes() {
+        assert_eq!(
+            url(parse("ntfy://alerts").unwrap()),
+            "https://ntfy.sh/alerts"
+        );
+        assert_eq!(
+            url(parse("ntfys://u:p@ntfy.example.org/alerts/").unwrap()),
+            "https://ntfy.example.org/alerts"
+        );
+        assert_eq!(
+            url(parse("json://localhost:8000/notify").unwrap()),
+            "http://localhost:8000/notify"
+        );
+        match parse("tgram://123:abc/-100/42").unwrap() {
+            Backend::Webhook(Webhook {
+                service: Service::Telegram { token, chats },
+                ..
+            }) => {
+                assert_eq!(token, "123:abc");
+                assert_eq!(chats, ["-100", "42"]);
+            }
+            _ => panic!("not a telegram url"),
+        }
+        assert!(parse("tgram://123:abc").is_err());
+        assert!(parse("mailto://user@example.org").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Renders short count strings onto square PNG keys, e.g. for Stream Deck buttons.
+
+/// 3x5 glyphs, one row per byte with the low three bits used.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 1, 1],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        '+' => [0, 2, 7, 2, 0],
+        '~' => [0, 0, 3, 6, 0],
+        '!' => [2, 2, 2, 0, 2],
+        _ => [0, 0, 0, 0, 0],
+    }
+}
+
+const BG: [u8; 3] = [0x1f, 0x1f, 0x1f];
+const FG: [u8; 3] = [0xff, 0xff, 0xff];
+const ACCENT: [u8; 3] = [0x00, 0x96, 0xfa];
+
+/// Draws `text` centered on a `size`x`size` RGB image and encodes it as PNG.
+pub fn render(text: &str, size: usize) -> Vec<u8> {
+    let chars: Vec<char> = text.chars().collect();
+    let cols = (chars.len() * 4).saturating_sub(1).max(1);
+    let scale = (size * 4 / 5 / cols).min(size / 2 / 5).max(1);
+    let (w, h) = (cols * scale, 5 * scale);
+    let (x0, y0) = (size.saturating_sub(w) / 2, size.saturating_sub(h) / 2);
+
+    let mut pixels = vec![BG; size * size];
+    // A bar along the bottom edge in the pixiv blue.
+    for p in &mut pixels[size * (size - size / 12)..] {
+        *p = ACCENT;
+    }
+    for (i, &c) in chars.iter().enumerate() {
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for col in 0..3 {
+                if bits & (4 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = x0 + (i * 4 + col) * scale + dx;
+                        let y = y0 + row * scale + dy;
+                        if x < size && y < size {
+                            pixels[y * size + x] = FG;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    encode(&pixels, size)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &x in data {
+        a = (a + x as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Minimal PNG encoder using stored (uncompressed) deflate blocks, which is plenty for tiny keys.
+fn encode(pixels: &[[u8; 3]], size: usize) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(size * (size * 3 + 1));
+    for row in pixels.chunks(size) {
+        raw.push(0);
+        raw.extend(row.iter().flatten());
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(size as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(size as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &ihdr);
+    chunk(&mut out, b"IDAT", &zlib);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
@@ -0,0 +1,14 @@
+//! Opens unread illusts in the browser, for the TUI and `POST /open`.
+
+use anyhow::{Context, Result};
+use pixiv::model::IllustId;
+
+use crate::wake::{self, Wake};
+
+/// Opens the pixiv page of `id` and tells the loop, which dismisses it with `dismiss_opened`.
+pub fn illust(id: IllustId, tx: &wake::Sender) -> Result<()> {
+    let url = format!("https://www.pixiv.net/artworks/{}", id);
+    open::that_detached(&url).with_context(|| format!("open {}", url))?;
+    tx.send(Wake::Opened(id))?;
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Result};
+use illust_notify::{process, Illust, Tracker, IMG_FILE};
+use pixiv::model::IllustId;
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+/// What the callback receives besides the positional arguments.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interface {
+    #[default]
+    Args,
+    /// Also export `ILLUST_*` environment variables.
+    Env,
+    /// Also export the variables and write a JSON [`Payload`] to stdin.
+    Json,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// Every refresh that is not held back by the cooldown or threshold.
+    Refresh,
+    /// A new alert, i.e. the anchor or count changed.
+    CountChanged,
+    Error,
+    ReauthNeeded,
+    Startup,
+    Shutdown,
+    /// A new illust matched `alert_keywords`, sent even while snoozed or below the threshold.
+    Keyword,
+    /// Pixiv is under maintenance or keeps failing, sent once until it recovers.
+    Outage,
+    /// The anchor has been out of reach of `max_pages` for `gap_report_hours`, sent once.
+    Gap,
+    /// A followed artist or watched tag entered one of the `rankings`.
+    Ranking,
+    /// One of the `searches` has new results.
+    Search,
+    /// The periodic `digest` of recommended works.
+    Digest,
+    /// A new article on Pixivision, see `pixivision`.
+    Pixivision,
+    /// A new paid post on Fanbox, see `fanbox`.
+    Fanbox,
+    /// A newer release is out, sent by `update --check`.
+    Update,
+    /// The current count, asked for by a hotkey or a `status` command.
+    Status,
+}
+
+impl Event {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Event::Refresh => "refresh",
+            Event::CountChanged => "count_changed",
+            Event::Error => "error",
+            Event::ReauthNeeded => "reauth_needed",
+            Event::Startup => "startup",
+            Event::Shutdown => "shutdown",
+            Event::Keyword => "keyword",
+            Event::Outage => "outage",
+            Event::Gap => "gap",
+            Event::Ranking => "ranking",
+            Event::Search => "search",
+            Event::Digest => "digest",
+            Event::Pixivision => "pixivision",
+            Event::Fanbox => "fanbox",
+            Event::Update => "update",
+            Event::Status => "status",
+        }
+    }
+
+    fn is_count(self) -> bool {
+        matches!(self, Event::Refresh | Event::CountChanged)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CallbackConfig {
+    pub path: String,
+    pub events: Vec<Event>,
+    #[serde(default)]
+    pub interface: Interface,
+}
+
+impl CallbackConfig {
+    /// The `./callback` script as it was run before `callbacks` existed.
+    pub fn legacy(interface: Interface) -> Self {
+        Self {
+            path: paths::callback_file().display().to_string(),
+            events: vec![
+                Event::Refresh,
+                Event::Error,
+                Event::ReauthNeeded,
+                Event::Keyword,
+                Event::Outage,
+                Event::Gap,
+                Event::Ranking,
+                Event::Search,
+                Event::Digest,
+                Event::Pixivision,
+                Event::Fanbox,
+                Event::Update,
+                Event::Status,
+            ],
+            interface,
+        }
+    }
+
+    pub fn run(&self, payload: &Payload) -> Result<()> {
+        let args = payload.args();
+        debug!("notify: {} {:?}", self.path, args);
+        let mut cmd = Command::new(&self.path);
+        cmd.args(&args);
+        if self.interface != Interface::Args {
+            cmd.envs(payload.env());
+        }
+        if self.interface == Interface::Json {
+            cmd.stdin(Stdio::piped());
+        }
+
+        let mut child = cmd.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Scripts that ignore stdin may exit before reading it all.
+            match stdin.write_all(&serde_json::to_vec(payload)?) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        let r = child.wait()?;
+        if r.success() {
+            debug!("notify: returned {:?}", r.code());
+        } else {
+            bail!("returned {:?}", r.code());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Payload<'a> {
+    pub event: Event,
+    pub message: Option<String>,
+    pub count: usize,
+    pub count_text: String,
+    pub severity: &'static str,
+    pub iid: IllustId,
+    pub since: String,
+    pub ago: String,
+    pub remain: bool,
+    pub skip: bool,
+    pub new_since_alert: usize,
+    pub reminder: bool,
+    /// Unread illusts posted within `window_hours`, if set.
+    pub window: Option<usize>,
+    /// Blurred per `blur_sanity_level`, see [`Payload::image_for`] for the original.
+    pub image: Option<PathBuf>,
+    /// Cached thumbnail of the newest unread illust, see `thumbs` in the tracker config.
+    pub thumb: Option<PathBuf>,
+    #[serde(skip)]
+    pub image_raw: Option<PathBuf>,
+    #[serde(skip)]
+    pub thumb_raw: Option<PathBuf>,
+    /// Title, artist and plain-text caption of the newest unread illust.
+    pub title: String,
+    pub artist: String,
+    pub caption: String,
+    /// Title and artist of the bookmarked anchor.
+    pub anchor_title: String,
+    pub anchor_artist: String,
+    pub unread: &'a [Illust],
+}
+
+fn flag(b: bool) -> String {
+    if b { "1" } else { "0" }.to_owned()
+}
+
+impl<'a> Payload<'a> {
+    pub fn new(app: &'a Tracker, event: Event) -> Self {
+        let newest = app.newest_shown();
+        let anchor = app.anchor.as_ref().filter(|i| app.shown(i));
+        let image = env::current_dir()
+            .ok()
+            .map(|d| d.join(IMG_FILE))
+            .filter(|p| p.exists());
+        let thumb = newest
+            .and_then(|i| app.thumb(i.id))
+            .and_then(|p| p.canonicalize().ok());
+        let blur = |path: &Option<PathBuf>, illust: Option<&Illust>| match path {
+            // Left out when it cannot be blurred, which loading the config warns about once.
+            Some(_) if illust.is_some_and(|i| app.blurred(i)) && !process::CAN_BLUR => None,
+            Some(p) if illust.is_some_and(|i| app.blurred(i)) => match process::blurred(p) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    warn!("blur {}: {:#}", p.display(), e);
+                    None
+                }
+            },
+            p => p.clone(),
+        };
+        Self {
+            event,
+            message: None,
+            count: app.dist(),
+            count_text: app.dist_text(),
+            severity: app.severity().as_str(),
+            iid: app.iid,
+            since: app.since(),
+            ago: app.since_ago(),
+            remain: app.remain,
+            skip: app.skip,
+            new_since_alert: 0,
+            reminder: false,
+            window: app.window_count(),
+            image: blur(&image, app.anchor.as_ref()),
+            thumb: blur(&thumb, newest),
+            image_raw: image,
+            thumb_raw: thumb,
+            title: newest.map(|i| i.title.clone()).unwrap_or_default(),
+            artist: newest.map(|i| i.user.name.clone()).unwrap_or_default(),
+            caption: newest.map(Illust::caption_text).unwrap_or_default(),
+            anchor_title: anchor.map(|i| i.title.clone()).unwrap_or_default(),
+            anchor_artist: anchor.map(|i| i.user.name.clone()).unwrap_or_default(),
+            unread: &app.unread,
+        }
+    }
+
+    /// A message without a tracker to describe, for reports from outside the main loop.
+    pub fn bare(event: Event, message: String) -> Payload<'static> {
+        Payload {
+            event,
+            message: Some(message),
+            count: 0,
+            count_text: String::new(),
+            severity: "",
+            iid: 0,
+            since: String::new(),
+            ago: String::new(),
+            remain: false,
+            skip: false,
+            new_since_alert: 0,
+            reminder: false,
+            window: None,
+            image: None,
+            thumb: None,
+            image_raw: None,
+            thumb_raw: None,
+            title: String::new(),
+            artist: String::new(),
+            caption: String::new(),
+            anchor_title: String::new(),
+            anchor_artist: String::new(),
+            unread: &[],
+        }
+    }
+
+    /// The anchor image, blurred unless the backend turned `blur` off.
+    pub fn image_for(&self, blur: bool) -> Option<&PathBuf> {
+        if blur { &self.image } else { &self.image_raw }.as_ref()
+    }
+
+    pub fn thumb_for(&self, blur: bool) -> Option<&PathBuf> {
+        if blur { &self.thumb } else { &self.thumb_raw }.as_ref()
+    }
+
+    /// Positional arguments: the counter for count events, otherwise the event name and message.
+    fn args(&self) -> Vec<String> {
+        if !self.event.is_count() {
+            let message = self.message.clone().unwrap_or_default();
+            return vec![self.event.as_str().to_owned(), message];
+        }
+        vec![
+            self.count.to_string(),
+            self.iid.to_string(),
+            self.since.clone(),
+            self.ago.clone(),
+            flag(self.remain),
+            flag(self.skip),
+            self.new_since_alert.to_string(),
+            flag(self.reminder),
+            self.count_text.clone(),
+            self.severity.to_owned(),
+        ]
+    }
+
+    fn env(&self) -> [(&'static str, String); 20] {
+        [
+            ("ILLUST_EVENT", self.event.as_str().to_owned()),
+            ("ILLUST_MESSAGE", self.message.clone().unwrap_or_default()),
+            ("ILLUST_COUNT", self.count.to_string()),
+            ("ILLUST_COUNT_TEXT", self.count_text.clone()),
+            ("ILLUST_SEVERITY", self.severity.to_owned()),
+            ("ILLUST_IID", self.iid.to_string()),
+            ("ILLUST_SINCE", self.since.clone()),
+            ("ILLUST_AGO", self.ago.clone()),
+            ("ILLUST_REMAIN", flag(self.remain)),
+            ("ILLUST_SKIP", flag(self.skip)),
+            ("ILLUST_NEW", self.new_since_alert.to_string()),
+            ("ILLUST_REMINDER", flag(self.reminder)),
+            (
+                "ILLUST_WINDOW",
+                self.window.map(|n| n.to_string()).unwrap_or_default(),
+            ),
+            (
+                "ILLUST_IMAGE",
+                self.image
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "ILLUST_THUMB",
+                self.thumb
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("ILLUST_TITLE", self.title.clone()),
+            ("ILLUST_ARTIST", self.artist.clone()),
+            ("ILLUST_CAPTION", self.caption.clone()),
+            ("ILLUST_ANCHOR_TITLE", self.anchor_title.clone()),
+            ("ILLUST_ANCHOR_ARTIST", self.anchor_artist.clone()),
+        ]
+    }
+}
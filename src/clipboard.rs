@@ -0,0 +1,65 @@
+//! Copies the newest unread illust to the clipboard when the count leaves zero.
+//!
+//! On X11 and Wayland the copy is served by this process, so it is kept open for the daemon's
+//! lifetime; a clipboard manager keeps the text past an exit.
+
+use anyhow::Result;
+use illust_notify::Tracker;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// `https://www.pixiv.net/artworks/<id>`
+    #[default]
+    Url,
+    /// `[title](url)`
+    Markdown,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClipboardConfig {
+    #[serde(default)]
+    format: Format,
+}
+
+pub struct Clipboard {
+    inner: Option<arboard::Clipboard>,
+    empty: bool,
+}
+
+impl Clipboard {
+    /// Starts from the current count, so a backlog at startup is not copied.
+    pub fn new(app: &Tracker) -> Self {
+        Self {
+            inner: None,
+            empty: app.dist() == 0,
+        }
+    }
+
+    /// Copies the newest unread illust if the count just went from zero to more.
+    pub fn update(&mut self, config: &ClipboardConfig, app: &Tracker) -> Result<()> {
+        let was_empty = std::mem::replace(&mut self.empty, app.dist() == 0);
+        if !was_empty || self.empty {
+            return Ok(());
+        }
+        let Some(illust) = app.newest_shown() else {
+            return Ok(());
+        };
+        let url = format!("https://www.pixiv.net/artworks/{}", illust.id);
+        let text = match config.format {
+            Format::Url => url,
+            Format::Markdown => {
+                let title = illust.title.replace('[', "\\[").replace(']', "\\]");
+                format!("[{}]({})", title, url)
+            }
+        };
+        let clipboard = match &mut self.inner {
+            Some(c) => c,
+            None => self.inner.insert(arboard::Clipboard::new()?),
+        };
+        clipboard.set_text(text)?;
+        info!("copied illust {} to the clipboard", illust.id);
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Keeps the last known state around so a panic does not lose it.
+//!
+//! A panic in the main loop writes the state as it was to `state.crash.json`, which the next
+//! start loads instead of an older `state.json`. The panic hook writes the snapshot taken
+//! after the latest refresh first, in case the state cannot be written as it was. Panics in
+//! spawned tasks only end those tasks, so they leave the file alone.
+
+use std::fs;
+use std::panic;
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, ThreadId};
+
+use anyhow::Result;
+use illust_notify::Tracker;
+
+use crate::secrets;
+
+pub const CRASH_FILE: &str = "state.crash.json";
+
+static SNAPSHOT: Mutex<Option<String>> = Mutex::new(None);
+static MAIN: OnceLock<ThreadId> = OnceLock::new();
+
+fn write(s: &str) {
+    match fs::write(CRASH_FILE, s) {
+        Ok(()) => eprintln!("state saved to {}", CRASH_FILE),
+        Err(e) => eprintln!("failed to save {}: {}", CRASH_FILE, e),
+    }
+}
+
+/// Installs the hook, to be called from the main task.
+pub fn install() {
+    let _ = MAIN.set(thread::current().id());
+    let prev = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // Spawned tasks run on the main thread as well, but inside a task.
+        let main = MAIN.get() == Some(&thread::current().id()) && tokio::task::try_id().is_none();
+        // The lock may be held by the panicking thread itself, so never wait for it.
+        if let Some(Ok(snapshot)) = main.then(|| SNAPSHOT.try_lock()) {
+            if let Some(s) = snapshot.as_deref() {
+                write(s);
+            }
+        }
+        prev(info);
+    }));
+}
+
+/// The state with the session left to the keyring if it is kept there.
+fn serialize(app: &Tracker, keyring: bool) -> Result<String> {
+    let mut v = app.snapshot()?;
+    if keyring {
+        if let Some(api) = v.as_object_mut().and_then(|m| m.get_mut("api")) {
+            *api = secrets::MARKER.into();
+        }
+    }
+    Ok(serde_json::to_string(&v)?)
+}
+
+/// Writes the state as it is after a panic was caught.
+pub fn dump(app: &Tracker, keyring: bool) {
+    match serialize(app, keyring) {
+        Ok(s) => write(&s),
+        Err(e) => eprintln!("failed to save {}: {:#}", CRASH_FILE, e),
+    }
+}
+
+/// Takes a snapshot to write if the state cannot be written as it is.
+pub fn update(app: &Tracker, keyring: bool) {
+    match serialize(app, keyring) {
+        Ok(s) => {
+            if let Ok(mut snapshot) = SNAPSHOT.lock() {
+                *snapshot = Some(s);
+            }
+        }
+        Err(e) => error!("snapshot: {:#}", e),
+    }
+}
+
+/// `state.crash.json` if it was written after `state.json`.
+pub fn newer_than(state: &str) -> bool {
+    let modified = |p| fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(CRASH_FILE), modified(state)) {
+        (Some(crash), Some(state)) => crash > state,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,99 @@
+//! A periodic digest of recommended works, the most bookmarked first.
+//!
+//! Sent as a `digest` event through the notifiers, and written to `file` if set.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use illust_notify::{Illust, Tracker, TrackerConfig};
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+
+use crate::export::{self, Format};
+
+fn default_top() -> usize {
+    10
+}
+
+fn default_days() -> u32 {
+    7
+}
+
+fn default_pool() -> usize {
+    90
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// What pixiv recommends for the account.
+    #[default]
+    Recommended,
+    /// Works related to the bookmarked anchor.
+    Related,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DigestConfig {
+    #[serde(default)]
+    source: Source,
+    /// Illusts in the digest.
+    #[serde(default = "default_top")]
+    top: usize,
+    /// Candidates fetched to pick `top` from.
+    #[serde(default = "default_pool")]
+    pool: usize,
+    #[serde(default = "default_days")]
+    every_days: u32,
+    /// Also write the digest here, as JSON or CSV per `format`.
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    format: Format,
+}
+
+impl DigestConfig {
+    pub fn due(&self, app: &Tracker) -> bool {
+        let every = Duration::days(self.every_days.into());
+        app.digest_at
+            .is_none_or(|t| OffsetDateTime::now_utc() - t >= every)
+    }
+
+    /// Picks the digest and returns the message to send.
+    pub async fn run(&self, app: &mut Tracker, config: &TrackerConfig) -> Result<String> {
+        let url = match self.source {
+            Source::Recommended => {
+                "https://app-api.pixiv.net/v1/illust/recommended?include_ranking_illusts=false&filter=for_android".to_owned()
+            }
+            Source::Related => {
+                let anchor = app.anchor.as_ref().context("no bookmarked anchor yet")?;
+                format!(
+                    "https://app-api.pixiv.net/v2/illust/related?illust_id={}&filter=for_android",
+                    anchor.id
+                )
+            }
+        };
+        let mut illusts: Vec<Illust> = app
+            .fetch(config, &url, self.pool)
+            .await?
+            .into_iter()
+            .filter(|i| app.shown(i) && !i.is_bookmarked)
+            .collect();
+        illusts.sort_by_key(|i| std::cmp::Reverse(i.total_bookmarks));
+        illusts.truncate(self.top);
+        app.digest_at = Some(OffsetDateTime::now_utc());
+
+        if let Some(file) = &self.file {
+            fs::write(file, export::render(&illusts, self.format)?)
+                .with_context(|| format!("write {}", file.display()))?;
+        }
+        let mut msg = format!("{} recommended works", illusts.len());
+        for i in &illusts {
+            msg += &format!(
+                "\n{} by {} ({} bookmarks) https://www.pixiv.net/artworks/{}",
+                i.title, i.user.name, i.total_bookmarks, i.id
+            );
+        }
+        Ok(msg)
+    }
+}
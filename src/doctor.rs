@@ -0,0 +1,106 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use illust_notify::{Page, Tracker, IMG_FILE};
+use pixiv::aapi::Restrict;
+
+use crate::{load_config, NOTIFY_FILE, STATE_FILE};
+
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn check<T>(&mut self, what: &str, r: Result<T>) -> Option<T> {
+        match r {
+            Ok(v) => {
+                println!("[ ok ] {}", what);
+                Some(v)
+            }
+            Err(e) => {
+                println!("[FAIL] {}: {:#}", what, e);
+                self.failed += 1;
+                None
+            }
+        }
+    }
+}
+
+/// Opens the file for appending without touching its content, removing it again if it was created.
+fn writable(path: &Path) -> Result<()> {
+    let existed = path.exists();
+    OpenOptions::new().append(true).create(true).open(path)?;
+    if !existed {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Resolves bare names through `PATH` the same way `Command` does.
+fn which(bin: &str) -> Result<PathBuf> {
+    if bin.contains('/') {
+        return Ok(PathBuf::from(bin));
+    }
+    let paths = env::var_os("PATH").unwrap_or_default();
+    match env::split_paths(&paths)
+        .map(|d| d.join(bin))
+        .find(|p| p.is_file())
+    {
+        Some(p) => Ok(p),
+        None => bail!("not found in PATH"),
+    }
+}
+
+fn executable(bin: &str) -> Result<()> {
+    let meta = fs::metadata(which(bin)?)?;
+    if !meta.is_file() {
+        bail!("not a file");
+    }
+    if meta.permissions().mode() & 0o111 == 0 {
+        bail!("not executable");
+    }
+    Ok(())
+}
+
+pub async fn run() -> Result<()> {
+    let mut report = Report::default();
+
+    if let Some(config) = report.check("config", load_config()) {
+        if let Some(app) = report.check(
+            "refresh token",
+            Tracker::new(&config.refresh_token, &config.tracker).await,
+        ) {
+            let r: Result<Page> = app
+                .api
+                .illust_follow(Restrict::Public)
+                .await
+                .map_err(Into::into);
+            if let Some(page) = report.check("follow feed", r) {
+                println!("       {} illusts on the first page", page.illusts.len());
+            }
+        }
+        for cb in config.callbacks() {
+            report.check(&format!("callback {}", cb.path), executable(&cb.path));
+        }
+        if let Some(bar) = &config.statusbar {
+            let path = bar.path();
+            if path != "-" {
+                report.check(&format!("write {}", path), writable(Path::new(path)));
+            }
+        }
+    }
+
+    for file in [STATE_FILE, IMG_FILE, NOTIFY_FILE] {
+        report.check(&format!("write {}", file), writable(Path::new(file)));
+    }
+
+    if report.failed > 0 {
+        bail!("{} checks failed", report.failed);
+    }
+    println!("all checks passed");
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! An append-only JSONL log of every count transition, for looking back at reading habits.
+//!
+//! Each line is one [`Entry`]. The file is rotated to `events.log.1`, `events.log.2`, ... once
+//! it grows past `max_kb`, keeping `keep` old files.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use illust_notify::Tracker;
+use pixiv::model::IllustId;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::time::Duration;
+
+fn default_path() -> PathBuf {
+    PathBuf::from("events.log")
+}
+
+fn default_max_kb() -> u64 {
+    1024
+}
+
+fn default_keep() -> u32 {
+    3
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EventLogConfig {
+    #[serde(default = "default_path")]
+    pub path: PathBuf,
+    #[serde(default = "default_max_kb")]
+    max_kb: u64,
+    #[serde(default = "default_keep")]
+    keep: u32,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            path: default_path(),
+            max_kb: default_max_kb(),
+            keep: default_keep(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Entry {
+    /// RFC 3339 in UTC.
+    pub time: String,
+    pub old_count: usize,
+    pub count: usize,
+    pub old_iid: IllustId,
+    pub iid: IllustId,
+    pub remain: bool,
+    pub skip: bool,
+    /// How long the refresh took, in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl Entry {
+    pub fn new(app: &Tracker, old: (IllustId, usize), duration: Duration) -> Self {
+        Self {
+            time: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            old_count: old.1,
+            count: app.dist(),
+            old_iid: old.0,
+            iid: app.iid,
+            remain: app.remain,
+            skip: app.skip,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+fn rotated(path: &Path, n: u32) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(format!(".{}", n));
+    PathBuf::from(s)
+}
+
+impl EventLogConfig {
+    pub fn append(&self, entry: &Entry) -> Result<()> {
+        let size = fs::metadata(&self.path).map_or(0, |m| m.len());
+        if size > self.max_kb * 1024 {
+            self.rotate()?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// All entries still kept, oldest first. Lines that fail to parse are skipped.
+    pub fn read(&self) -> Result<Vec<Entry>> {
+        let mut files: Vec<PathBuf> = (1..=self.keep)
+            .rev()
+            .map(|n| rotated(&self.path, n))
+            .collect();
+        files.push(self.path.clone());
+        let mut entries = Vec::new();
+        for path in files {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            entries.extend(text.lines().filter_map(|l| serde_json::from_str(l).ok()));
+        }
+        Ok(entries)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+        for n in (1..self.keep).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        debug!("rotated {}", self.path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! Dumps the unread backlog as JSON or CSV, e.g. to archive it before marking it read.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use illust_notify::Illust;
+use pixiv::model::IllustId;
+use serde::{Deserialize, Serialize};
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Row<'a> {
+    id: IllustId,
+    title: &'a str,
+    artist: &'a str,
+    artist_id: u64,
+    url: String,
+    create_date: &'a str,
+}
+
+impl<'a> Row<'a> {
+    fn new(illust: &'a Illust) -> Self {
+        Self {
+            id: illust.id,
+            title: &illust.title,
+            artist: &illust.user.name,
+            artist_id: illust.user.id,
+            url: format!("https://www.pixiv.net/artworks/{}", illust.id),
+            create_date: &illust.create_date,
+        }
+    }
+}
+
+/// The part of `state.json` needed when no daemon is reachable.
+#[derive(Deserialize, Debug)]
+pub struct Backlog {
+    #[serde(default)]
+    pub unread: Vec<Illust>,
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Renders the unread illusts, newest first.
+pub fn render(unread: &[Illust], format: Format) -> Result<String> {
+    let rows = unread.iter().map(Row::new);
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(&rows.collect::<Vec<_>>())? + "\n",
+        Format::Csv => {
+            let mut r = "id,title,artist,artist_id,url,create_date\r\n".to_owned();
+            for row in rows {
+                r += &format!(
+                    "{},{},{},{},{},{}\r\n",
+                    row.id,
+                    csv_field(row.title),
+                    csv_field(row.artist),
+                    row.artist_id,
+                    row.url,
+                    csv_field(row.create_date)
+                );
+            }
+            r
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
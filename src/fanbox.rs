@@ -0,0 +1,112 @@
+//! Watches the Fanbox home feed for new posts by supported and followed creators.
+//!
+//! Fanbox does not accept the AppAPI session, so it needs the `FANBOXSESSID` cookie of a
+//! browser login, or `"keyring"` to look it up under the `fanbox` account. The first poll only
+//! records the newest post; later ones are sent as `fanbox` events labeled as such.
+
+use anyhow::{Context, Result};
+use illust_notify::Tracker;
+use serde::Deserialize;
+use tokio::time::{Duration, Instant};
+
+use crate::secrets;
+
+fn default_interval() -> u64 {
+    1800
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FanboxConfig {
+    session: String,
+    /// Also report posts anyone can read, only paid ones by default.
+    #[serde(default)]
+    free: bool,
+    /// Seconds between polls.
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Post {
+    id: String,
+    title: String,
+    fee_required: u32,
+    creator_id: String,
+    user: Creator,
+}
+
+#[derive(Deserialize, Debug)]
+struct Creator {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Items {
+    items: Vec<Post>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    body: Items,
+}
+
+pub struct Fanbox {
+    client: reqwest::Client,
+    next: Option<Instant>,
+}
+
+impl Fanbox {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            next: None,
+        }
+    }
+
+    /// When the next poll is due.
+    pub fn next(&self) -> Instant {
+        self.next.unwrap_or_else(Instant::now)
+    }
+
+    /// Describes the posts published since the last poll, oldest first.
+    pub async fn poll(&mut self, app: &mut Tracker, config: &FanboxConfig) -> Result<Vec<String>> {
+        self.next = Some(Instant::now() + Duration::from_secs(config.interval));
+        let session = secrets::resolve(&config.session, secrets::FANBOX)?;
+        let r: Response = self
+            .client
+            .get("https://api.fanbox.cc/post.listHome?limit=10")
+            .timeout(app.request_timeout())
+            .header("Cookie", format!("FANBOXSESSID={}", session))
+            .header("Origin", "https://www.fanbox.cc")
+            .send()
+            .await?
+            .error_for_status()
+            .context("fanbox session")?
+            .json()
+            .await?;
+
+        let last = app.fanbox;
+        let mut msgs = Vec::new();
+        for post in r.body.items.iter().rev() {
+            let id: u64 = post.id.parse().unwrap_or_default();
+            if id <= last {
+                continue;
+            }
+            app.fanbox = app.fanbox.max(id);
+            if last == 0 || (post.fee_required == 0 && !config.free) {
+                continue;
+            }
+            let fee = if post.fee_required > 0 {
+                format!("¥{}", post.fee_required)
+            } else {
+                "free".to_owned()
+            };
+            msgs.push(format!(
+                "[Fanbox] {}: {} ({}) https://www.fanbox.cc/@{}/posts/{}",
+                post.user.name, post.title, fee, post.creator_id, post.id
+            ));
+        }
+        Ok(msgs)
+    }
+}
@@ -0,0 +1,137 @@
+//! Schedules the feeds polled besides the follow feed, each on its own interval.
+//!
+//! The follow feed keeps to `delay`, while rankings, searches, Pixivision, Fanbox and sync wake
+//! the loop on their own `*_interval` or `interval`. Polls falling due within [`COALESCE`] of
+//! each other, or of the next follow refresh, are served by a single wakeup.
+
+use std::collections::VecDeque;
+
+use illust_notify::Tracker;
+use tokio::time::{Duration, Instant};
+
+use crate::callback::Event;
+use crate::notifier::{self, Notifier};
+use crate::wake::Wake;
+use crate::{fanbox, fire, pixivision, ranking, search, sync, Config};
+
+/// How early a poll may run to share a wakeup with another.
+pub const COALESCE: Duration = Duration::from_secs(30);
+
+pub struct Feeds {
+    rankings: ranking::Rankings,
+    searches: search::Searches,
+    articles: pixivision::Pixivision,
+    fanbox: fanbox::Fanbox,
+    sync: sync::Sync,
+}
+
+impl Feeds {
+    pub fn new() -> Self {
+        Self {
+            rankings: Default::default(),
+            searches: Default::default(),
+            articles: Default::default(),
+            fanbox: fanbox::Fanbox::new(),
+            sync: sync::Sync::new(),
+        }
+    }
+
+    /// When the earliest poll is due, `None` if no feed is configured.
+    pub fn next(&self, config: &Config) -> Option<Instant> {
+        [
+            self.rankings.next(&config.rankings),
+            self.searches.next(&config.searches),
+            config.pixivision.as_ref().map(|_| self.articles.next()),
+            config.fanbox.as_ref().map(|_| self.fanbox.next()),
+            config.sync.as_ref().map(|_| self.sync.next()),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Polls the feeds due by now or shortly after, returning whether the searches were
+    /// counted again.
+    pub async fn poll(
+        &mut self,
+        app: &mut Tracker,
+        config: &Config,
+        notifiers: &mut [Box<dyn Notifier>],
+        queued: &mut VecDeque<Wake>,
+    ) -> bool {
+        let at = Instant::now() + COALESCE;
+        let due = |next: Option<Instant>| next.is_some_and(|t| t <= at);
+
+        if due(self.rankings.next(&config.rankings)) {
+            let interval = Duration::from_secs(config.ranking_interval);
+            match self
+                .rankings
+                .poll(app, &config.tracker, &config.rankings, interval)
+                .await
+            {
+                Ok(msgs) => {
+                    for msg in msgs {
+                        info!("ranking: {}", msg);
+                        fire(notifiers, app, Event::Ranking, msg).await;
+                    }
+                }
+                Err(e) => error!("rankings: {:#}", e),
+            }
+        }
+        let searched = due(self.searches.next(&config.searches));
+        if searched {
+            let interval = Duration::from_secs(config.search_interval);
+            match self
+                .searches
+                .poll(app, &config.tracker, &config.searches, interval)
+                .await
+            {
+                Ok(msgs) => {
+                    for msg in msgs {
+                        info!("search: {}", msg);
+                        fire(notifiers, app, Event::Search, msg).await;
+                    }
+                }
+                Err(e) => error!("searches: {:#}", e),
+            }
+        }
+        if let Some(pv) = config.pixivision.as_ref() {
+            if due(Some(self.articles.next())) {
+                match self.articles.poll(app, &config.tracker, pv).await {
+                    Ok(new) => {
+                        for article in new {
+                            info!("pixivision: {}", article.title);
+                            let payload = pv.payload(app, &article);
+                            notifier::dispatch(notifiers, app, &payload).await;
+                        }
+                    }
+                    Err(e) => error!("pixivision: {:#}", e),
+                }
+            }
+        }
+        if let Some(sc) = config.sync.as_ref() {
+            if due(Some(self.sync.next())) {
+                match self.sync.poll(app, sc).await {
+                    // Count from the adopted anchor right away.
+                    Ok(true) => queued.push_back(Wake::ManualRefresh),
+                    Ok(false) => {}
+                    Err(e) => error!("sync: {:#}", e),
+                }
+            }
+        }
+        if let Some(fc) = config.fanbox.as_ref() {
+            if due(Some(self.fanbox.next())) {
+                match self.fanbox.poll(app, fc).await {
+                    Ok(msgs) => {
+                        for msg in msgs {
+                            info!("{}", msg);
+                            fire(notifiers, app, Event::Fanbox, msg).await;
+                        }
+                    }
+                    Err(e) => error!("fanbox: {:#}", e),
+                }
+            }
+        }
+        searched
+    }
+}
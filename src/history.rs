@@ -0,0 +1,148 @@
+//! `illust-notify history`: the count over time from the event log, printed without the API.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+use crate::eventlog::{Entry, EventLogConfig};
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const WIDTH: usize = 60;
+
+/// Counts of one day.
+#[derive(Default)]
+struct Day {
+    min: usize,
+    max: usize,
+    last: usize,
+    bookmarks: usize,
+}
+
+fn span(d: Duration) -> String {
+    let m = d.whole_minutes();
+    if m >= 60 {
+        format!("{}h {}m", m / 60, m % 60)
+    } else {
+        format!("{}m", m)
+    }
+}
+
+/// The count as one bar per bucket, each the highest count seen in it.
+fn sparkline(
+    points: &[(OffsetDateTime, usize)],
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+) -> String {
+    let step = Duration::seconds((to - from).whole_seconds() / WIDTH as i64);
+    let mut buckets = vec![None; WIDTH];
+    // The count holds until the next transition.
+    let mut i = 0;
+    let mut current = None;
+    for (b, bucket) in buckets.iter_mut().enumerate() {
+        let end = from + step * (b as i32 + 1);
+        let mut high = current;
+        while i < points.len() && points[i].0 < end {
+            current = Some(points[i].1);
+            high = high.max(current);
+            i += 1;
+        }
+        *bucket = high;
+    }
+    let top = buckets.iter().flatten().max().copied().unwrap_or(0).max(1);
+    buckets
+        .into_iter()
+        .map(|b| match b {
+            Some(n) => BARS[(n * (BARS.len() - 1) + top / 2) / top],
+            None => ' ',
+        })
+        .collect()
+}
+
+pub fn print(log: &EventLogConfig, days: u32, tz: UtcOffset) -> Result<()> {
+    let now = OffsetDateTime::now_utc();
+    let from = now
+        .checked_sub(Duration::days(days.into()))
+        .with_context(|| format!("{} days is too far back", days))?;
+    let entries = log.read()?;
+    let points: Vec<(OffsetDateTime, &Entry)> = entries
+        .iter()
+        .filter_map(|e| {
+            Some((
+                OffsetDateTime::parse(&e.time, &Rfc3339).ok()?.to_offset(tz),
+                e,
+            ))
+        })
+        .filter(|(t, _)| *t >= from)
+        .collect();
+    if points.is_empty() {
+        println!(
+            "no transitions in {} over the last {} days",
+            log.path.display(),
+            days
+        );
+        return Ok(());
+    }
+
+    let counts: Vec<_> = points.iter().map(|(t, e)| (*t, e.count)).collect();
+    let top = counts.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    println!("last {} days, at most {}", days, top);
+    println!("{}", sparkline(&counts, from, now));
+    println!();
+
+    let day_fmt = format_description!("[year]-[month]-[day]");
+    let mut by_day: BTreeMap<String, Day> = BTreeMap::new();
+    for (t, e) in &points {
+        let day = by_day.entry(t.format(&day_fmt)?).or_insert_with(|| Day {
+            min: e.count,
+            ..Default::default()
+        });
+        day.min = day.min.min(e.count);
+        day.max = day.max.max(e.count);
+        day.last = e.count;
+        if e.iid != e.old_iid {
+            day.bookmarks += 1;
+        }
+    }
+    println!(
+        "{:<10} {:>6} {:>6} {:>6} {:>9}",
+        "date", "min", "max", "last", "bookmarks"
+    );
+    for (date, d) in &by_day {
+        println!(
+            "{:<10} {:>6} {:>6} {:>6} {:>9}",
+            date, d.min, d.max, d.last, d.bookmarks
+        );
+    }
+
+    // From the first unread illust to catching up again.
+    let fmt = format_description!("[month]-[day] [hour]:[minute]");
+    let mut out = String::new();
+    let mut started: Option<(OffsetDateTime, usize)> = None;
+    for (t, e) in &points {
+        match started {
+            None if e.old_count == 0 && e.count > 0 => started = Some((*t, e.count)),
+            Some((at, peak)) if e.count == 0 => {
+                writeln!(
+                    out,
+                    "  {}  after {}, peak {}",
+                    t.format(&fmt)?,
+                    span(*t - at),
+                    peak
+                )?;
+                started = None;
+            }
+            Some((at, peak)) => started = Some((at, peak.max(e.count))),
+            None => {}
+        }
+    }
+    if !out.is_empty() {
+        println!();
+        println!("caught up:");
+        print!("{}", out);
+    }
+    Ok(())
+}
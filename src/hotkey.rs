@@ -0,0 +1,62 @@
+//! Global hotkeys for a daemon without a window, through `global-hotkey`.
+//!
+//! `refresh` refreshes and then announces the count as a `status` event, `status` announces it
+//! right away. Keys are written like `ctrl+alt+p` and registered once at startup.
+
+use std::thread;
+
+use anyhow::{Context, Result};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use serde::Deserialize;
+
+use crate::wake::{self, Wake};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HotkeyConfig {
+    refresh: Option<String>,
+    status: Option<String>,
+}
+
+/// Registers the hotkeys, which stay registered as long as the returned manager lives.
+pub fn register(config: &HotkeyConfig, tx: wake::Sender) -> Result<GlobalHotKeyManager> {
+    let manager = GlobalHotKeyManager::new()?;
+    let mut refresh = None;
+    let mut status = None;
+    for (key, id) in [
+        (&config.refresh, &mut refresh),
+        (&config.status, &mut status),
+    ] {
+        if let Some(key) = key {
+            let hotkey = key
+                .parse::<HotKey>()
+                .with_context(|| format!("hotkey {:?}", key))?;
+            manager
+                .register(hotkey)
+                .with_context(|| format!("register {:?}", key))?;
+            *id = Some(hotkey.id());
+        }
+    }
+    // The events arrive on a blocking channel of the crate's own.
+    thread::spawn(move || {
+        while let Ok(e) = GlobalHotKeyEvent::receiver().recv() {
+            if e.state() != HotKeyState::Pressed {
+                continue;
+            }
+            let wakes = if Some(e.id()) == refresh {
+                vec![Wake::ManualRefresh, Wake::Status]
+            } else if Some(e.id()) == status {
+                vec![Wake::Status]
+            } else {
+                continue;
+            };
+            debug!("hotkey: {:?}", wakes);
+            for w in wakes {
+                if tx.send(w).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(manager)
+}
@@ -0,0 +1,108 @@
+//! Pushes the count and refresh stats after each refresh in the InfluxDB line protocol.
+//!
+//! `url` is the full write endpoint, e.g. `http://host:8086/api/v2/write?org=o&bucket=b` for
+//! InfluxDB 2 or `http://host:8428/write` for VictoriaMetrics, so Grafana can chart the backlog
+//! without scraping `/metrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Result;
+use illust_notify::{FeedDelta, Tracker};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tokio::time::Duration;
+
+use crate::secrets;
+
+fn default_measurement() -> String {
+    "illust_notify".to_owned()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct InfluxConfig {
+    url: String,
+    /// Sent as `Authorization: Token ...`, or `"keyring"` for the `influx` account.
+    token: Option<String>,
+    #[serde(default = "default_measurement")]
+    measurement: String,
+    /// Extra tags on every point, e.g. `host`.
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+/// Escapes a measurement, tag key or tag value.
+fn escape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            r.push('\\');
+        }
+        r.push(c);
+    }
+    r
+}
+
+impl InfluxConfig {
+    /// One point for a refresh, `delta` is `None` when it failed.
+    fn line(&self, app: &Tracker, delta: Option<&FeedDelta>, took: Duration) -> String {
+        let mut line = escape(&self.measurement);
+        for (k, v) in &self.tags {
+            let _ = write!(line, ",{}={}", escape(k), escape(v));
+        }
+        let api = app.api_usage();
+        let _ = write!(
+            line,
+            " ok={},duration_ms={}i,api_hour={}i,api_day={}i",
+            delta.is_some(),
+            took.as_millis(),
+            api.hour,
+            api.day
+        );
+        if let Some(d) = delta {
+            let _ = write!(
+                line,
+                ",count={}i,remain={},skip={},pages={}i,cached={},anchor={}i",
+                d.after, app.remain, app.skip, d.pages, d.cached, app.iid
+            );
+        }
+        let _ = write!(
+            line,
+            " {}",
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        );
+        line
+    }
+}
+
+pub struct Influx {
+    client: reqwest::Client,
+}
+
+impl Influx {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn push(
+        &self,
+        config: &InfluxConfig,
+        app: &Tracker,
+        delta: Option<&FeedDelta>,
+        took: Duration,
+    ) -> Result<()> {
+        let mut req = self
+            .client
+            .post(&config.url)
+            .timeout(app.request_timeout())
+            .body(config.line(app, delta, took));
+        if let Some(token) = &config.token {
+            let token = secrets::resolve(token, secrets::INFLUX)?;
+            req = req.header("Authorization", format!("Token {}", token));
+        }
+        req.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
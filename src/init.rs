@@ -0,0 +1,125 @@
+//! `illust-notify init`: asks for what a first `config.json` needs, writes it and tries it.
+//!
+//! JSON has no comments, so the prompts carry the explanations instead and the file only holds
+//! what was answered; everything else keeps its default.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use anyhow::{bail, Context, Result};
+use illust_notify::Tracker;
+use serde_json::{json, Map, Value};
+
+use crate::{doctor, load_config, paths, save_state, secrets, STATE_FILE};
+
+fn ask(question: &str, default: &str) -> Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        bail!("stdin closed");
+    }
+    let line = line.trim();
+    Ok(if line.is_empty() { default } else { line }.to_owned())
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let answer = ask(question, if default { "Y/n" } else { "y/N" })?;
+    Ok(match answer.to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+fn number(question: &str, default: u32) -> Result<u32> {
+    loop {
+        match ask(question, &default.to_string())?.parse() {
+            Ok(n) => return Ok(n),
+            Err(e) => println!("  {}", e),
+        }
+    }
+}
+
+pub async fn run(force: bool) -> Result<()> {
+    let path = paths::config_file();
+    if !force && path.exists() {
+        bail!("{} exists, pass --force to replace it", path.display());
+    }
+    let mut config = Map::new();
+
+    println!("A refresh token can be obtained with e.g. gppt or pixiv_auth.py.");
+    let token = loop {
+        let token = ask("refresh token", "")?;
+        if !token.is_empty() {
+            break token;
+        }
+    };
+    if cfg!(feature = "keyring") && confirm("keep it in the OS keyring", true)? {
+        secrets::set(secrets::REFRESH_TOKEN, &token)?;
+        config.insert("refresh_token".to_owned(), secrets::MARKER.into());
+        config.insert("keyring".to_owned(), true.into());
+    } else {
+        config.insert("refresh_token".to_owned(), token.into());
+    }
+
+    println!("Each refresh walks the follow feed until the last bookmark, 30 illusts a page.");
+    config.insert(
+        "delay".to_owned(),
+        number("seconds between refreshes", 300)?.into(),
+    );
+    config.insert(
+        "max_pages".to_owned(),
+        number("pages per refresh at most", 5)?.into(),
+    );
+
+    println!("The ./callback script next to config.json runs on every count change by default.");
+    if confirm("notify through Termux:API", false)? {
+        config.insert("termux".to_owned(), json!({}));
+    }
+    if confirm("post to a Matrix room", false)? {
+        config.insert(
+            "matrix".to_owned(),
+            json!({
+                "homeserver": ask("  homeserver", "https://matrix.org")?,
+                "access_token": ask("  access token", "")?,
+                "room": ask("  room id or alias", "")?,
+            }),
+        );
+    }
+    if confirm(
+        "serve the count over HTTP, for Home Assistant or a widget",
+        false,
+    )? {
+        config.insert(
+            "http".to_owned(),
+            json!({ "listen": ask("  listen on", "127.0.0.1:8977")? }),
+        );
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&Value::Object(config))?)?;
+    println!("wrote {}", path.display());
+
+    if force || fs::metadata(STATE_FILE).is_err() {
+        println!("Counting starts from your newest bookmark, otherwise from the first refresh.");
+        let user = ask("your pixiv user id, empty to skip", "")?;
+        if !user.is_empty() {
+            let user = user.parse::<u64>().context("user id")?;
+            let config = load_config()?;
+            let mut app = Tracker::new(&config.refresh_token, &config.tracker).await?;
+            app.seed(&config.tracker, user, false).await?;
+            println!("anchored at {} since {}", app.iid, app.since());
+            save_state(app, &config)?;
+        }
+    }
+
+    println!();
+    doctor::run().await
+}
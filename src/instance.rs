@@ -0,0 +1,61 @@
+//! Keeps a second daemon from running against the same state directory.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, Write};
+use std::{fs, process};
+
+use anyhow::{bail, Context, Result};
+use fd_lock::{RwLock, RwLockWriteGuard};
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::server::{self, HttpConfig};
+
+const LOCK_FILE: &str = "lock";
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Held for the lifetime of the daemon, the lock is released when the process exits.
+pub type Guard = RwLockWriteGuard<'static, File>;
+
+fn try_lock() -> Result<Option<Guard>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(LOCK_FILE)?;
+    let lock = Box::leak(Box::new(RwLock::new(file)));
+    let mut guard = match lock.try_write() {
+        Ok(guard) => guard,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    guard.set_len(0)?;
+    guard.rewind()?;
+    writeln!(guard, "{}", process::id())?;
+    Ok(Some(guard))
+}
+
+/// Takes the lock, first asking the running instance to shut down when `takeover` is set.
+pub async fn lock(takeover: bool, http: Option<&HttpConfig>) -> Result<Guard> {
+    if let Some(guard) = try_lock()? {
+        return Ok(guard);
+    }
+    let pid = fs::read_to_string(LOCK_FILE).unwrap_or_default();
+    let pid = pid.trim();
+    if !takeover {
+        bail!(
+            "another instance (pid {}) is running here, stop it or pass --takeover",
+            pid
+        );
+    }
+    let http = http.context("--takeover needs http.listen to reach the running instance")?;
+    info!("asking pid {} to shut down", pid);
+    server::post(http, "/shutdown")?;
+    let deadline = Instant::now() + TAKEOVER_TIMEOUT;
+    while Instant::now() < deadline {
+        sleep(Duration::from_millis(500)).await;
+        if let Some(guard) = try_lock()? {
+            return Ok(guard);
+        }
+    }
+    bail!("pid {} did not shut down in {:?}", pid, TAKEOVER_TIMEOUT)
+}
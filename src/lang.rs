@@ -0,0 +1,220 @@
+//! The built-in notification texts in each `language` the relative times come in.
+//!
+//! Templates fill in `{count}`, `{since}`, `{ago}`, `{new}`, `{title}`, `{artist}` and
+//! `{illust}`; user templates in the config are left as written.
+
+pub struct Messages {
+    /// The count with the anchor date, for tooltips and status replies.
+    summary: &'static str,
+    /// The count alone, for notification titles.
+    count: &'static str,
+    since: &'static str,
+    new_since_alert: &'static str,
+    by: &'static str,
+    /// The newest unread illust, as filled in by [`Messages::by`].
+    newest: &'static str,
+}
+
+const EN: Messages = Messages {
+    summary: "{count} illusts since {since} ({ago})",
+    count: "{count} illusts",
+    since: "since {since} ({ago})",
+    new_since_alert: ", +{new} since last alert",
+    by: "{title} by {artist}",
+    newest: "Newest: {illust}",
+};
+
+const JA: Messages = Messages {
+    summary: "{since}（{ago}）以降 {count} 件",
+    count: "{count} 件",
+    since: "{since}（{ago}）以降",
+    new_since_alert: "、前回の通知から +{new}",
+    by: "{artist}「{title}」",
+    newest: "最新: {illust}",
+};
+
+const ZH: Messages = Messages {
+    summary: "自 {since}（{ago}）以来 {count} 幅作品",
+    count: "{count} 幅作品",
+    since: "自 {since}（{ago}）以来",
+    new_since_alert: "，较上次提醒 +{new}",
+    by: "{artist}《{title}》",
+    newest: "最新：{illust}",
+};
+
+const DE: Messages = Messages {
+    summary: "{count} Werke seit {since} ({ago})",
+    count: "{count} Werke",
+    since: "seit {since} ({ago})",
+    new_since_alert: ", +{new} seit der letzten Meldung",
+    by: "{title} von {artist}",
+    newest: "Neuestes: {illust}",
+};
+
+const FR: Messages = Messages {
+    summary: "{count} illustrations depuis {since} ({ago})",
+    count: "{count} illustrations",
+    since: "depuis {since} ({ago})",
+    new_since_alert: ", +{new} depuis la dernière alerte",
+    by: "{title} par {artist}",
+    newest: "Dernière : {illust}",
+};
+
+const RU: Messages = Messages {
+    summary: "Новых работ: {count} с {since} ({ago})",
+    count: "Новых работ: {count}",
+    since: "с {since} ({ago})",
+    new_since_alert: ", +{new} с прошлого уведомления",
+    by: "{title}, автор {artist}",
+    newest: "Новейшая: {illust}",
+};
+
+const ES: Messages = Messages {
+    summary: "{count} ilustraciones desde {since} ({ago})",
+    count: "{count} ilustraciones",
+    since: "desde {since} ({ago})",
+    new_since_alert: ", +{new} desde el último aviso",
+    by: "{title} de {artist}",
+    newest: "Más reciente: {illust}",
+};
+
+const IT: Messages = Messages {
+    summary: "{count} illustrazioni dal {since} ({ago})",
+    count: "{count} illustrazioni",
+    since: "dal {since} ({ago})",
+    new_since_alert: ", +{new} dall'ultimo avviso",
+    by: "{title} di {artist}",
+    newest: "Più recente: {illust}",
+};
+
+const PT: Messages = Messages {
+    summary: "{count} ilustrações desde {since} ({ago})",
+    count: "{count} ilustrações",
+    since: "desde {since} ({ago})",
+    new_since_alert: ", +{new} desde o último alerta",
+    by: "{title} de {artist}",
+    newest: "Mais recente: {illust}",
+};
+
+const PL: Messages = Messages {
+    summary: "Nowe prace: {count} od {since} ({ago})",
+    count: "Nowe prace: {count}",
+    since: "od {since} ({ago})",
+    new_since_alert: ", +{new} od ostatniego powiadomienia",
+    by: "{title}, autor: {artist}",
+    newest: "Najnowsza: {illust}",
+};
+
+const SV: Messages = Messages {
+    summary: "{count} verk sedan {since} ({ago})",
+    count: "{count} verk",
+    since: "sedan {since} ({ago})",
+    new_since_alert: ", +{new} sedan senaste aviseringen",
+    by: "{title} av {artist}",
+    newest: "Senaste: {illust}",
+};
+
+const TR: Messages = Messages {
+    summary: "{since} ({ago}) tarihinden beri {count} eser",
+    count: "{count} eser",
+    since: "{since} ({ago}) tarihinden beri",
+    new_since_alert: ", son bildirimden beri +{new}",
+    by: "{artist} - {title}",
+    newest: "En yeni: {illust}",
+};
+
+const UK: Messages = Messages {
+    summary: "Нових робіт: {count} з {since} ({ago})",
+    count: "Нових робіт: {count}",
+    since: "з {since} ({ago})",
+    new_since_alert: ", +{new} з останнього сповіщення",
+    by: "{title}, автор {artist}",
+    newest: "Найновіша: {illust}",
+};
+
+/// The texts for `lang`, English for languages without them.
+pub fn messages(lang: Option<&str>) -> &'static Messages {
+    match lang.unwrap_or("en") {
+        "ja" => &JA,
+        "zh" => &ZH,
+        "de" => &DE,
+        "fr" => &FR,
+        "ru" => &RU,
+        "es" => &ES,
+        "it" => &IT,
+        "pt" => &PT,
+        "pl" => &PL,
+        "sv" => &SV,
+        "tr" => &TR,
+        "uk" => &UK,
+        _ => &EN,
+    }
+}
+
+/// Fills in one pass, so values are never filled in themselves.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut r = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find('{') {
+        r.push_str(&rest[..i]);
+        rest = &rest[i..];
+        match values.iter().find(|(k, _)| rest.starts_with(k)) {
+            Some((k, v)) => {
+                r.push_str(v);
+                rest = &rest[k.len()..];
+            }
+            None => {
+                r.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    r.push_str(rest);
+    r
+}
+
+impl Messages {
+    pub fn summary(&self, count: &str, since: &str, ago: &str) -> String {
+        fill(
+            self.summary,
+            &[("{count}", count), ("{since}", since), ("{ago}", ago)],
+        )
+    }
+
+    pub fn count(&self, count: &str) -> String {
+        fill(self.count, &[("{count}", count)])
+    }
+
+    pub fn since(&self, since: &str, ago: &str) -> String {
+        fill(self.since, &[("{since}", since), ("{ago}", ago)])
+    }
+
+    pub fn new_since_alert(&self, new: usize) -> String {
+        fill(self.new_since_alert, &[("{new}", &new.to_string())])
+    }
+
+    pub fn by(&self, title: &str, artist: &str) -> String {
+        fill(self.by, &[("{title}", title), ("{artist}", artist)])
+    }
+
+    pub fn newest(&self, illust: &str) -> String {
+        fill(self.newest, &[("{illust}", illust)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_once() {
+        let m = messages(None);
+        assert_eq!(
+            m.summary("3", "01-02", "1h ago"),
+            "3 illusts since 01-02 (1h ago)"
+        );
+        assert_eq!(m.by("{artist}", "a"), "{artist} by a");
+        assert_eq!(messages(Some("ja")).count("5+"), "5+ 件");
+        assert_eq!(messages(Some("xx")).count("1"), "1 illusts");
+    }
+}
@@ -0,0 +1,94 @@
+//! Unread count badge on the dock icon through the Unity LauncherEntry D-Bus API, which is
+//! understood by Plasma's task manager, Dash to Dock and Plank.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use illust_notify::Tracker;
+use serde::Deserialize;
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+use crate::callback::{Event, Payload};
+use crate::notifier::Notifier;
+
+const OBJECT_PATH: &str = "/com/canonical/unity/launcherentry/illust_notify";
+const INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+
+fn default_desktop_id() -> String {
+    "illust-notify.desktop".to_owned()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LauncherConfig {
+    /// The `.desktop` file whose dock icon gets the badge.
+    #[serde(default = "default_desktop_id")]
+    desktop_id: String,
+}
+
+pub struct Launcher {
+    config: LauncherConfig,
+    /// Docks key the badge by the sender's bus name and drop it once the name is gone, so the
+    /// connection is kept for as long as the badge should show.
+    conn: Option<Connection>,
+}
+
+impl Launcher {
+    pub fn new(config: LauncherConfig) -> Self {
+        Self { config, conn: None }
+    }
+
+    async fn update(&mut self, count: usize, visible: bool) -> Result<()> {
+        let conn = match &self.conn {
+            Some(conn) => conn.clone(),
+            None => self.conn.insert(Connection::session().await?).clone(),
+        };
+        let app_uri = format!("application://{}", self.config.desktop_id);
+        debug!("launcher: count {} visible {}", count, visible);
+        let props = HashMap::from([
+            ("count", Value::from(count as i64)),
+            ("count-visible", Value::from(visible)),
+        ]);
+        let r = conn
+            .emit_signal(
+                None::<&str>,
+                OBJECT_PATH,
+                INTERFACE,
+                "Update",
+                &(app_uri, props),
+            )
+            .await;
+        if r.is_err() {
+            // Reconnect next time, the bus may have restarted.
+            self.conn = None;
+        }
+        Ok(r?)
+    }
+}
+
+impl Notifier for Launcher {
+    fn name(&self) -> &str {
+        "launcher"
+    }
+
+    fn accepts(&self, event: Event) -> bool {
+        matches!(
+            event,
+            Event::Refresh | Event::CountChanged | Event::Shutdown
+        )
+    }
+
+    fn notify<'a>(
+        &'a mut self,
+        _: &'a Tracker,
+        payload: &'a Payload<'_>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        let (count, visible) = match payload.event {
+            Event::Shutdown => (0, false),
+            _ => (payload.count, payload.count > 0),
+        };
+        self.update(count, visible).boxed_local()
+    }
+}
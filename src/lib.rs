@@ -0,0 +1,1478 @@
+//! Tracks how many illusts in the Pixiv follow feed are newer than the last bookmarked one.
+//!
+//! [`Tracker`] owns the API client and the persisted [`TrackerState`]; [`Tracker::refresh`]
+//! walks the feed and reports what changed as a [`FeedDelta`].
+
+#[macro_use]
+extern crate log;
+
+pub mod lang;
+pub mod process;
+pub mod thumbs;
+pub mod trace;
+pub mod visited;
+pub mod web;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::io::{Seek, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use pixiv::aapi::Restrict;
+use pixiv::client::{AuthedClient, AuthedState};
+use pixiv::download::DownloadClient;
+use pixiv::model::IllustId;
+use process::ProcessConfig;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thumbs::ThumbsConfig;
+use time::format_description::OwnedFormatItem;
+use time::{format_description, macros::format_description, OffsetDateTime, UtcOffset};
+use time_tz::{Offset, TimeZone};
+use timeago::BoxedLanguage;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use visited::Visited;
+
+/// Thumbnail of the anchor illust, relative to the working directory.
+pub const IMG_FILE: &str = "img.jpg";
+
+fn default_max_pages() -> u32 {
+    5
+}
+
+fn default_min_skip_pages() -> u32 {
+    3
+}
+
+fn default_request_timeout() -> u32 {
+    30
+}
+
+fn default_metered_max_pages() -> u32 {
+    1
+}
+
+fn default_restrict() -> String {
+    "public".to_owned()
+}
+
+/// What ends the scan of the follow feed.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorMode {
+    /// The newest bookmarked illust.
+    #[default]
+    Bookmark,
+    /// The newest illust when "mark read" was last requested, bookmarks are ignored.
+    MarkRead,
+    /// Whichever of the two is newer, so both bookmarking and marking read zero the count.
+    Hybrid,
+}
+
+/// Which of the sizes pixiv offers to download, see [`Illust::image_url`].
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageSize {
+    /// 360x360, cropped to a square.
+    #[default]
+    SquareMedium,
+    /// Up to 540 on the longer side.
+    Medium,
+    /// Up to 1200 on the longer side.
+    Large,
+    /// The first page at full resolution, often several megabytes.
+    Original,
+}
+
+/// The part of `config.json` the tracker reads.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrackerConfig {
+    #[serde(default = "default_max_pages")]
+    pub max_pages: u32,
+    #[serde(default = "default_min_skip_pages")]
+    pub min_skip_pages: u32,
+    /// Of the relative times and the built-in notification texts, see [`lang::messages`].
+    pub language: Option<String>,
+    pub date_format: Option<String>,
+    pub timezone: Option<String>,
+    /// Seconds before a single API call or download chunk is abandoned.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u32,
+    /// Start in the low-bandwidth mode: no image downloads, fewer pages and a longer delay.
+    #[serde(default)]
+    pub metered: bool,
+    #[serde(default = "default_metered_max_pages")]
+    pub metered_max_pages: u32,
+    /// While the anchor is beyond `max_pages`, double the pages of each following refresh up to
+    /// this many, then fall back to `max_pages`.
+    pub max_pages_cap: Option<u32>,
+    #[serde(default)]
+    pub anchor: AnchorMode,
+    /// Also count the unread illusts posted in the last this many hours.
+    pub window_hours: Option<u32>,
+    /// Make the `window_hours` count the main one, for a "what's new today" ticker.
+    #[serde(default)]
+    pub count_window: bool,
+    /// Most API calls in any hour; past 80% refreshes fall back to `metered_max_pages`.
+    pub api_budget_hour: Option<u32>,
+    /// Most API calls in any day, throttled the same way.
+    pub api_budget_day: Option<u32>,
+    /// Keep thumbnails of all unread illusts, not just the anchor, for the backends showing them.
+    pub thumbs: Option<ThumbsConfig>,
+    /// Size of `img.jpg` and of the images attached to notifications.
+    #[serde(default)]
+    pub image_size: ImageSize,
+    /// Resize and convert `img.jpg` after each download.
+    pub process: Option<ProcessConfig>,
+    /// Illusts above this `sanity_level` are counted but never shown, see [`Tracker::shown`].
+    pub max_sanity_level: Option<u32>,
+    /// Never show R-18 works or anything above the all-ages `sanity_level` of 2.
+    #[serde(default)]
+    pub sfw_only: bool,
+    /// `restrict` of the follow feed, `public`, `private` or `all`; other values are passed on
+    /// as they are, for scopes pixiv adds later.
+    #[serde(default = "default_restrict")]
+    pub restrict: String,
+    /// Walk this feed instead of the follow feed, e.g.
+    /// `https://app-api.pixiv.net/v1/illust/mypixiv` for mypixiv friends.
+    pub feed_url: Option<String>,
+    /// `PHPSESSID` cookie of a browser login, to walk the website feed while the refresh
+    /// token fails.
+    pub web_session: Option<String>,
+    /// Blur the images of illusts above this `sanity_level` that are still shown, unless the
+    /// backend sets `"blur": false`. A blurred image is left out when built without `image`.
+    pub blur_sanity_level: Option<u32>,
+}
+
+const HOUR: Duration = Duration::from_secs(3600);
+const DAY: Duration = Duration::from_secs(86400);
+
+/// API calls made in the last hour and day.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApiUsage {
+    pub hour: u32,
+    pub day: u32,
+}
+
+/// What a refresh changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedDelta {
+    /// The bookmarked anchor moved to another illust.
+    pub anchor_changed: bool,
+    /// Unread count before the refresh.
+    pub before: usize,
+    /// Unread count after the refresh.
+    pub after: usize,
+    /// The first page was unchanged, so the scan was skipped.
+    pub cached: bool,
+    /// Pages walked, 1 when cached.
+    pub pages: u32,
+    /// Walked the website feed as the AppAPI session failed, see [`TrackerConfig::web_session`].
+    pub web: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ImageUrls {
+    pub square_medium: String,
+    /// Missing from unread lists saved by older versions, like the other sizes.
+    #[serde(default)]
+    pub medium: String,
+    #[serde(default)]
+    pub large: String,
+}
+
+/// The original of a single-page illust.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MetaSinglePage {
+    pub original_image_url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MetaPageUrls {
+    #[serde(default)]
+    pub original: String,
+}
+
+/// A page of a multi-page illust.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MetaPage {
+    pub image_urls: MetaPageUrls,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub is_followed: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub translated_name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Illust {
+    pub id: IllustId,
+    pub title: String,
+    pub create_date: String,
+    pub is_bookmarked: bool,
+    pub image_urls: ImageUrls,
+    /// The artist, missing from unread lists saved by older versions.
+    #[serde(default)]
+    pub user: User,
+    /// HTML as pixiv returns it, see [`Illust::caption_text`].
+    #[serde(default)]
+    pub caption: String,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub meta_single_page: MetaSinglePage,
+    #[serde(default)]
+    pub meta_pages: Vec<MetaPage>,
+    /// 2 for all ages, higher for more suggestive works; 0 in lists saved by older versions.
+    #[serde(default)]
+    pub sanity_level: u32,
+    /// 1 for R-18, 2 for R-18G.
+    #[serde(default)]
+    pub x_restrict: u32,
+    #[serde(default)]
+    pub total_bookmarks: u64,
+}
+
+impl Illust {
+    /// The URL of the image in `size`, falling back to `square_medium` when it is unknown.
+    pub fn image_url(&self, size: ImageSize) -> &str {
+        let original = || {
+            let single = self.meta_single_page.original_image_url.as_deref();
+            let first = self
+                .meta_pages
+                .first()
+                .map(|p| p.image_urls.original.as_str());
+            single.or(first).unwrap_or_default()
+        };
+        let url = match size {
+            ImageSize::SquareMedium => "",
+            ImageSize::Medium => &self.image_urls.medium,
+            ImageSize::Large => &self.image_urls.large,
+            ImageSize::Original => original(),
+        };
+        if url.is_empty() {
+            &self.image_urls.square_medium
+        } else {
+            url
+        }
+    }
+
+    /// The caption as plain text, with line breaks kept and other markup dropped.
+    pub fn caption_text(&self) -> String {
+        let mut r = String::with_capacity(self.caption.len());
+        let mut rest = self.caption.as_str();
+        while let Some(i) = rest.find('<') {
+            r.push_str(&rest[..i]);
+            let Some(j) = rest[i..].find('>') else {
+                rest = &rest[i..];
+                break;
+            };
+            if rest[i + 1..].starts_with("br") {
+                r.push('\n');
+            }
+            rest = &rest[i + j + 1..];
+        }
+        r.push_str(rest);
+        r.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&")
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Page {
+    pub illusts: Vec<Illust>,
+    pub next_url: Option<String>,
+}
+
+impl Page {
+    /// Changes whenever an illust is added, removed or (un)bookmarked.
+    fn fingerprint(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        for illust in &self.illusts {
+            (illust.id, illust.is_bookmarked).hash(&mut h);
+        }
+        h.finish()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TrackerState {
+    pub iid: IllustId,
+    pub since: OffsetDateTime,
+    pub remain: bool,
+    pub skip: bool,
+    #[serde(with = "visited")]
+    pub vis: Visited,
+    #[serde(default)]
+    pub unread: Vec<Illust>,
+    /// Newest illust id seen by a complete scan, later scans stop once they reach it.
+    #[serde(default)]
+    pub max_seen: IllustId,
+    /// The newest illust when "mark read" was last requested, 0 if never.
+    #[serde(default)]
+    pub mark: IllustId,
+    /// The bookmarked illust at `iid`, missing from states saved by older versions.
+    #[serde(default)]
+    pub anchor: Option<Illust>,
+    /// The illust `IMG_FILE` was downloaded for, it lags behind `iid` in the metered mode
+    /// and on battery.
+    #[serde(default)]
+    pub image_iid: IllustId,
+    /// Tag search feeds by search word.
+    #[serde(default)]
+    pub searches: BTreeMap<String, SearchState>,
+    /// When the last digest was sent, see `digest` in `config.json`.
+    #[serde(default)]
+    pub digest_at: Option<OffsetDateTime>,
+    /// The newest Pixivision article seen, 0 before the first poll.
+    #[serde(default)]
+    pub pixivision: u64,
+    /// The newest Fanbox post seen, likewise.
+    #[serde(default)]
+    pub fanbox: u64,
+    /// When `iid` or `mark` last changed, for the last-writer-wins of `sync`.
+    #[serde(default)]
+    pub changed_at: Option<OffsetDateTime>,
+    /// Unread illusts dismissed with [`Tracker::dismiss`], left out of later scans until the
+    /// anchor passes them.
+    #[serde(default, with = "visited")]
+    pub opened: Visited,
+}
+
+/// The part of the state `sync` shares between machines, see [`Tracker::shared`].
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SharedState {
+    pub iid: IllustId,
+    pub since: OffsetDateTime,
+    pub mark: IllustId,
+    pub anchor: Option<Illust>,
+    #[serde(with = "visited")]
+    pub vis: Visited,
+}
+
+/// A tag search followed besides the follow feed, counted from its own mark.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SearchState {
+    /// The newest result when the search was added or last marked read.
+    pub mark: IllustId,
+    /// The newest result of the last poll.
+    pub newest: IllustId,
+    /// Results newer than `mark`.
+    pub count: usize,
+}
+
+impl Default for TrackerState {
+    fn default() -> Self {
+        Self {
+            iid: 0,
+            since: OffsetDateTime::UNIX_EPOCH,
+            remain: false,
+            skip: false,
+            vis: Visited::default(),
+            unread: Vec::new(),
+            max_seen: 0,
+            mark: 0,
+            anchor: None,
+            image_iid: 0,
+            searches: BTreeMap::new(),
+            digest_at: None,
+            pixivision: 0,
+            fanbox: 0,
+            changed_at: None,
+            opened: Visited::default(),
+        }
+    }
+}
+
+/// Context attached to failures of the token refresh.
+#[derive(Debug)]
+pub struct AuthFailed;
+
+impl fmt::Display for AuthFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("authentication failed")
+    }
+}
+
+/// A failure on pixiv's side rather than ours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outage {
+    /// The scheduled maintenance page, served with a 503.
+    Maintenance,
+    /// Any other 5xx response.
+    ServerError,
+}
+
+impl Outage {
+    /// Told apart by the error text, as the client does not expose response statuses.
+    pub fn detect(e: &anyhow::Error) -> Option<Self> {
+        let text = format!("{:#}", e).to_lowercase();
+        if text.contains("maintenance") {
+            return Some(Outage::Maintenance);
+        }
+        let status = text
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|w| w.len() == 3 && w.starts_with('5') && w.bytes().all(|b| b.is_ascii_digit()));
+        status.then_some(Outage::ServerError)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outage::Maintenance => "maintenance",
+            Outage::ServerError => "server errors",
+        }
+    }
+}
+
+/// How much the unread count can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Exact,
+    /// The scan was cut short by `min_skip_pages`, reusing ids from earlier refreshes.
+    Approximate,
+    /// `max_pages` was reached before the anchor, so the count is a lower bound.
+    LowerBound,
+    /// Both of the above.
+    Unreliable,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Exact => "exact",
+            Severity::Approximate => "approximate",
+            Severity::LowerBound => "lower_bound",
+            Severity::Unreliable => "unreliable",
+        }
+    }
+}
+
+/// Layout version of [`TrackerDump`], bump it with a migration step in [`TrackerDump::parse`].
+pub const STATE_VERSION: u64 = 1;
+
+#[derive(Deserialize, Serialize)]
+pub struct TrackerDump {
+    /// Missing in dumps written before versioning, which count as 0.
+    #[serde(default)]
+    version: u64,
+    api: AuthedState,
+    #[serde(flatten)]
+    state: TrackerState,
+}
+
+/// A borrowed [`TrackerDump`], serialized the same way.
+#[derive(Serialize)]
+struct DumpRef<'a> {
+    version: u64,
+    api: &'a AuthedState,
+    #[serde(flatten)]
+    state: &'a TrackerState,
+}
+
+impl TrackerDump {
+    /// Parses a dump written by this or an older version.
+    ///
+    /// A state that still fails to parse is reset, but the authed session is kept so an upgrade
+    /// never forces a fresh login.
+    pub fn parse(v: serde_json::Value) -> Result<Self> {
+        let version = v.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+        if version > STATE_VERSION {
+            bail!(
+                "state version {} is newer than the supported {}",
+                version,
+                STATE_VERSION
+            );
+        }
+        // Version 0 only lacks fields that have serde defaults, so it needs no step yet.
+        if version < STATE_VERSION {
+            info!(
+                "migrating state from version {} to {}",
+                version, STATE_VERSION
+            );
+        }
+        let api = v.get("api").cloned().context("missing api")?;
+        let mut dump = match serde_json::from_value::<Self>(v) {
+            Ok(dump) => dump,
+            Err(e) => {
+                warn!("state: {}, starting over with the saved session", e);
+                Self {
+                    version: STATE_VERSION,
+                    api: serde_json::from_value(api)?,
+                    state: Default::default(),
+                }
+            }
+        };
+        dump.version = STATE_VERSION;
+        Ok(dump)
+    }
+}
+
+pub struct Tracker {
+    pub api: AuthedClient,
+    pub state: TrackerState,
+    pub downloader: DownloadClient,
+    pub tz: UtcOffset,
+    /// When `tz` was resolved, it is resolved again hourly to follow DST and zone changes.
+    tz_at: Instant,
+    date_format: Option<OwnedFormatItem>,
+    ago: timeago::Formatter<BoxedLanguage>,
+    messages: &'static lang::Messages,
+    /// See [`TrackerConfig::request_timeout`].
+    request_timeout: Duration,
+    /// Low-bandwidth mode, see [`TrackerConfig::metered`].
+    metered: bool,
+    /// Running on battery, which pauses image downloads like the metered mode.
+    on_battery: bool,
+    /// Fingerprint of the first page of the last complete scan.
+    head: Option<u64>,
+    /// Scan up to the anchor or `max_pages` regardless of earlier scans.
+    full: bool,
+    /// When the API calls of the last day were made, oldest first.
+    calls: VecDeque<Instant>,
+    /// Set to end the scan at the next page boundary, see [`Tracker::stopper`].
+    stop: Arc<AtomicBool>,
+    /// How many times `max_pages` is doubled, see [`TrackerConfig::max_pages_cap`].
+    escalation: u32,
+    /// The cap was reached without finding the anchor, wait until it is found by other means.
+    escalation_exhausted: bool,
+    /// See [`TrackerConfig::window_hours`] and [`TrackerConfig::count_window`].
+    window_hours: Option<u32>,
+    count_window: bool,
+    /// The cutoff date and the pause between pages of a running [`Tracker::backfill`].
+    backfill: Option<(OffsetDateTime, Duration)>,
+    thumbs: Option<ThumbsConfig>,
+    image_size: ImageSize,
+    max_sanity_level: Option<u32>,
+    sfw_only: bool,
+    blur_sanity_level: Option<u32>,
+}
+
+impl Deref for Tracker {
+    type Target = TrackerState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl DerefMut for Tracker {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.state
+    }
+}
+
+const DATE_FORMAT: &[format_description::FormatItem<'static>] =
+    format_description!("[month padding:none]/[day padding:none] [hour padding:none]:[minute]");
+
+const OFFSET_FORMAT: &[format_description::FormatItem<'static>] =
+    format_description!("[offset_hour sign:mandatory]:[offset_minute]");
+
+fn parse_tz(tz: &str) -> Result<UtcOffset> {
+    if let Some(tz) = time_tz::timezones::get_by_name(tz) {
+        return Ok(tz.get_offset_utc(&OffsetDateTime::now_utc()).to_utc());
+    }
+    Ok(UtcOffset::parse(tz, &OFFSET_FORMAT)?)
+}
+
+/// The offset of the system zone, looked up by name as `current_local_offset` fails once the
+/// process has more than one thread.
+fn local_tz() -> Result<UtcOffset> {
+    match time_tz::system::get_timezone() {
+        Ok(tz) => Ok(tz.get_offset_utc(&OffsetDateTime::now_utc()).to_utc()),
+        Err(e) => {
+            debug!("system timezone: {}", e);
+            Ok(UtcOffset::current_local_offset()?)
+        }
+    }
+}
+
+fn try_resolve_tz(tz: Option<&str>) -> Result<UtcOffset> {
+    match tz {
+        None | Some("local") => local_tz(),
+        Some("UTC") | Some("utc") => Ok(UtcOffset::UTC),
+        Some(tz) => parse_tz(tz),
+    }
+}
+
+pub fn resolve_tz(tz: Option<&str>) -> UtcOffset {
+    try_resolve_tz(tz).unwrap_or_else(|e| {
+        warn!("timezone {:?}: {:#?}, using UTC", tz, e);
+        UtcOffset::UTC
+    })
+}
+
+fn date_format(config: &TrackerConfig) -> Option<OwnedFormatItem> {
+    let fmt = config.date_format.as_deref()?;
+    format_description::parse_owned::<2>(fmt)
+        .inspect_err(|e| warn!("date format {:?}: {}, using default", fmt, e))
+        .ok()
+}
+
+fn ago_language(lang: &str) -> Option<BoxedLanguage> {
+    use timeago::languages::*;
+    Some(match lang {
+        "en" => boxup(english::English),
+        "ja" => boxup(japanese::Japanese),
+        "zh" => boxup(chinese::Chinese),
+        "de" => boxup(german::German),
+        "fr" => boxup(french::French),
+        "ru" => boxup(russian::Russian),
+        "es" => boxup(spanish::Spanish),
+        "it" => boxup(italian::Italian),
+        "pt" => boxup(portuguese::Portuguese),
+        "pl" => boxup(polish::Polish),
+        "sv" => boxup(swedish::Swedish),
+        "tr" => boxup(turkish::Turkish),
+        "uk" => boxup(ukrainian::Ukrainian),
+        _ => return None,
+    })
+}
+
+fn ago_formatter(config: &TrackerConfig) -> timeago::Formatter<BoxedLanguage> {
+    let lang = config.language.as_deref().unwrap_or("en");
+    let lang = ago_language(lang).unwrap_or_else(|| {
+        warn!("unsupported language {:?}, using en", lang);
+        timeago::languages::boxup(timeago::languages::english::English)
+    });
+    timeago::Formatter::with_language(lang)
+}
+
+/// Bounds a request by `config.request_timeout`, a hung connection would stall the loop otherwise.
+async fn timed<T, E: Into<anyhow::Error>>(
+    config: &TrackerConfig,
+    what: &str,
+    f: impl Future<Output = Result<T, E>>,
+) -> Result<T> {
+    bounded(Duration::from_secs(config.request_timeout.into()), what, f).await
+}
+
+async fn bounded<T, E: Into<anyhow::Error>>(
+    d: Duration,
+    what: &str,
+    f: impl Future<Output = Result<T, E>>,
+) -> Result<T> {
+    let start = trace::now();
+    let r = match timeout(d, f).await {
+        Ok(r) => r.map_err(Into::into),
+        Err(_) => Err(anyhow::Error::msg(format!(
+            "{} timed out after {}s",
+            what,
+            d.as_secs()
+        ))),
+    };
+    trace::record(what, start, r.is_ok(), Vec::new());
+    r
+}
+
+impl Tracker {
+    pub async fn new(refresh_token: &str, config: &TrackerConfig) -> Result<Self> {
+        Ok(Self {
+            api: AuthedClient::new(refresh_token).await?,
+            state: Default::default(),
+            downloader: DownloadClient::new(),
+            tz: resolve_tz(config.timezone.as_deref()),
+            tz_at: Instant::now(),
+            date_format: date_format(config),
+            ago: ago_formatter(config),
+            messages: lang::messages(config.language.as_deref()),
+            request_timeout: Duration::from_secs(config.request_timeout.into()),
+            metered: config.metered,
+            on_battery: false,
+            head: None,
+            full: false,
+            calls: VecDeque::new(),
+            stop: Default::default(),
+            escalation: 0,
+            escalation_exhausted: false,
+            window_hours: config.window_hours,
+            count_window: config.count_window,
+            backfill: None,
+            thumbs: config.thumbs.clone(),
+            image_size: config.image_size,
+            max_sanity_level: config.max_sanity_level,
+            sfw_only: config.sfw_only,
+            blur_sanity_level: config.blur_sanity_level,
+        })
+    }
+
+    pub fn load(dump: TrackerDump, config: &TrackerConfig) -> Result<Self> {
+        Ok(Self {
+            api: AuthedClient::load(dump.api),
+            state: dump.state,
+            downloader: DownloadClient::new(),
+            tz: resolve_tz(config.timezone.as_deref()),
+            tz_at: Instant::now(),
+            date_format: date_format(config),
+            ago: ago_formatter(config),
+            messages: lang::messages(config.language.as_deref()),
+            request_timeout: Duration::from_secs(config.request_timeout.into()),
+            metered: config.metered,
+            on_battery: false,
+            head: None,
+            full: false,
+            calls: VecDeque::new(),
+            stop: Default::default(),
+            escalation: 0,
+            escalation_exhausted: false,
+            window_hours: config.window_hours,
+            count_window: config.count_window,
+            backfill: None,
+            thumbs: config.thumbs.clone(),
+            image_size: config.image_size,
+            max_sanity_level: config.max_sanity_level,
+            sfw_only: config.sfw_only,
+            blur_sanity_level: config.blur_sanity_level,
+        })
+    }
+
+    /// Applies the display settings of a reloaded config.
+    pub fn configure(&mut self, config: &TrackerConfig) {
+        self.reload_tz(config);
+        self.date_format = date_format(config);
+        self.ago = ago_formatter(config);
+        self.messages = lang::messages(config.language.as_deref());
+        self.request_timeout = Duration::from_secs(config.request_timeout.into());
+        self.window_hours = config.window_hours;
+        self.count_window = config.count_window;
+        self.thumbs = config.thumbs.clone();
+        self.image_size = config.image_size;
+        self.max_sanity_level = config.max_sanity_level;
+        self.sfw_only = config.sfw_only;
+        self.blur_sanity_level = config.blur_sanity_level;
+    }
+
+    /// Signs in again with a `refresh_token` changed by a config reload.
+    pub async fn reauth(&mut self, refresh_token: &str) -> Result<()> {
+        self.api = self.timed("auth", AuthedClient::new(refresh_token)).await?;
+        Ok(())
+    }
+
+    pub fn dump(self) -> TrackerDump {
+        TrackerDump {
+            version: STATE_VERSION,
+            api: self.api.state,
+            state: self.state,
+        }
+    }
+
+    /// The dump as JSON, leaving the tracker usable.
+    pub fn snapshot(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(DumpRef {
+            version: STATE_VERSION,
+            api: &self.api.state,
+            state: &self.state,
+        })?)
+    }
+
+    fn convert_date(&self, date: &str) -> Result<OffsetDateTime> {
+        let t = OffsetDateTime::parse(date, &format_description::well_known::Iso8601::DEFAULT)?
+            .to_offset(self.tz);
+        Ok(t)
+    }
+
+    /// Follows a DST switch or a change of the system zone within the hour.
+    fn update_tz(&mut self, config: &TrackerConfig) {
+        if self.tz_at.elapsed() >= HOUR {
+            self.reload_tz(config);
+        }
+    }
+
+    fn reload_tz(&mut self, config: &TrackerConfig) {
+        self.tz_at = Instant::now();
+        // Keeping the last offset beats switching to UTC.
+        let tz = match try_resolve_tz(config.timezone.as_deref()) {
+            Ok(tz) => tz,
+            Err(e) => {
+                warn!(
+                    "timezone {:?}: {:#}, keeping {}",
+                    config.timezone, e, self.tz
+                );
+                return;
+            }
+        };
+        if tz != self.tz {
+            info!("timezone offset changed to {}", tz);
+            self.tz = tz;
+        }
+    }
+
+    pub fn since(&self) -> String {
+        // In the current offset, which may differ from the one `since` was converted to.
+        let since = self.since.to_offset(self.tz);
+        let r = match &self.date_format {
+            Some(fmt) => since.format(fmt),
+            None => since.format(&DATE_FORMAT),
+        };
+        match r {
+            Ok(s) => s,
+            Err(e) => format!("Error: {:?}", e),
+        }
+    }
+
+    pub fn since_ago(&self) -> String {
+        // A clock set back could put the anchor in the future.
+        let d = (OffsetDateTime::now_utc() - self.since).max(time::Duration::ZERO);
+        self.ago.convert(d.unsigned_abs())
+    }
+
+    /// The built-in texts in the configured `language`.
+    pub fn messages(&self) -> &'static lang::Messages {
+        self.messages
+    }
+
+    /// The count with the anchor date, e.g. `12 illusts since 5/1 10:00 (3 hours ago)`.
+    pub fn summary(&self) -> String {
+        self.messages
+            .summary(&self.dist_text(), &self.since(), &self.since_ago())
+    }
+
+    pub fn metered(&self) -> bool {
+        self.metered
+    }
+
+    pub fn on_battery(&self) -> bool {
+        self.on_battery
+    }
+
+    pub fn set_on_battery(&mut self, on: bool) {
+        self.on_battery = on;
+    }
+
+    /// Whether images and thumbnails are fetched, not in the metered mode or on battery.
+    pub fn downloads(&self) -> bool {
+        !self.metered && !self.on_battery
+    }
+
+    /// Switches the low-bandwidth mode, rescanning on the next refresh.
+    pub fn set_metered(&mut self, on: bool) {
+        self.metered = on;
+        self.head = None;
+    }
+
+    /// A flag that makes an in-flight refresh stop before its next page and keep what it has
+    /// counted so far, for shutting down without abandoning it.
+    pub fn stopper(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    fn record_call(&mut self) {
+        let now = Instant::now();
+        while self.calls.front().is_some_and(|t| now - *t >= DAY) {
+            self.calls.pop_front();
+        }
+        self.calls.push_back(now);
+    }
+
+    pub fn api_usage(&self) -> ApiUsage {
+        let now = Instant::now();
+        let within = |d| self.calls.iter().filter(|t| now - **t < d).count() as u32;
+        ApiUsage {
+            hour: within(HOUR),
+            day: within(DAY),
+        }
+    }
+
+    /// Whether either budget is 80% used.
+    pub fn throttled(&self, config: &TrackerConfig) -> bool {
+        let usage = self.api_usage();
+        let near = |used: u32, budget: Option<u32>| budget.is_some_and(|b| used * 5 >= b * 4);
+        near(usage.hour, config.api_budget_hour) || near(usage.day, config.api_budget_day)
+    }
+
+    /// How long until a call fits in the budgets again, `None` if one does now.
+    pub fn budget_wait(&self, config: &TrackerConfig) -> Option<Duration> {
+        let now = Instant::now();
+        let wait = |window: Duration, budget: Option<u32>| {
+            let budget = budget? as usize;
+            let recent: Vec<_> = self.calls.iter().filter(|t| now - **t < window).collect();
+            // Enough calls have to age out that the next refresh fits.
+            let t = recent.get(recent.len().checked_sub(budget)?)?;
+            Some(window - (now - **t))
+        };
+        let hour = wait(HOUR, config.api_budget_hour);
+        let day = wait(DAY, config.api_budget_day);
+        hour.max(day)
+    }
+
+    /// Moves the marks of all tag searches to their newest result.
+    pub fn mark_searches_read(&mut self) {
+        for s in self.state.searches.values_mut() {
+            s.mark = s.newest;
+            s.count = 0;
+        }
+    }
+
+    /// Anchors at the newest illust seen, zeroing the count until newer ones arrive.
+    pub fn mark_read(&mut self) -> Result<()> {
+        let Some(newest) = self.unread.first().cloned() else {
+            return Ok(());
+        };
+        info!("marked read up to {}", newest.id);
+        self.since = self.convert_date(&newest.create_date)?;
+        self.iid = newest.id;
+        self.mark = newest.id;
+        self.changed_at = Some(OffsetDateTime::now_utc());
+        self.anchor = Some(newest);
+        self.vis = Visited::default();
+        self.opened = Visited::default();
+        self.unread.clear();
+        self.remain = false;
+        self.skip = false;
+        self.head = None;
+        Ok(())
+    }
+
+    /// Drops the unread illust `id` from the count and the list for good, e.g. once it has been
+    /// opened; returns whether it was unread.
+    pub fn dismiss(&mut self, id: IllustId) -> bool {
+        if !self.vis.remove(id) {
+            return false;
+        }
+        self.unread.retain(|i| i.id != id);
+        self.opened.insert(id);
+        true
+    }
+
+    /// Makes the next refresh scan up to the anchor, ignoring what earlier scans have seen.
+    pub fn invalidate(&mut self) {
+        self.head = None;
+        self.full = true;
+    }
+
+    pub fn shared(&self) -> SharedState {
+        SharedState {
+            iid: self.iid,
+            since: self.since,
+            mark: self.mark,
+            anchor: self.anchor.clone(),
+            vis: self.vis.clone(),
+        }
+    }
+
+    /// Takes the anchor another machine moved at `at`, the unread illusts are counted again by
+    /// the next refresh.
+    pub fn adopt(&mut self, shared: SharedState, at: OffsetDateTime) {
+        info!("adopted anchor {} from sync", shared.iid);
+        self.iid = shared.iid;
+        self.since = shared.since;
+        self.mark = shared.mark;
+        self.anchor = shared.anchor;
+        self.vis = shared.vis;
+        self.unread.clear();
+        self.remain = false;
+        self.skip = false;
+        self.changed_at = Some(at);
+        self.invalidate();
+    }
+
+    /// Anchors at the newest bookmark of `user_id`, so a fresh state has a date before it has
+    /// walked the feed.
+    pub async fn seed(
+        &mut self,
+        config: &TrackerConfig,
+        user_id: u64,
+        private: bool,
+    ) -> Result<()> {
+        timed(config, "auth", self.api.ensure_authed())
+            .await
+            .context(AuthFailed)?;
+        let url = format!(
+            "https://app-api.pixiv.net/v1/user/bookmarks/illust?user_id={}&restrict={}",
+            user_id,
+            if private { "private" } else { "public" }
+        );
+        self.record_call();
+        let r: Page = timed(config, "bookmarks", self.api.call_url(&url)).await?;
+        let illust = r.illusts.into_iter().next().context("no bookmarks")?;
+        info!("seeded from {}: {}", illust.id, illust.title);
+        self.since = self.convert_date(&illust.create_date)?;
+        self.iid = illust.id;
+        self.anchor = Some(illust);
+        Ok(())
+    }
+
+    /// The website feed up to the anchor or `max_pages` as a single page, and whether it ends
+    /// at the anchor or the end of the feed. Fails with `e` without a `web_session`.
+    async fn web_feed(&self, config: &TrackerConfig, e: anyhow::Error) -> Result<(Page, bool)> {
+        let Some(session) = &config.web_session else {
+            return Err(e);
+        };
+        warn!("falling back to the website feed: {:#}", e);
+        let client = web::WebClient::default();
+        let mut illusts = Vec::new();
+        let mut complete = false;
+        for p in 1..=config.max_pages {
+            let (page, last) = timed(config, "web feed", client.follow(session, p)).await?;
+            let reached = page
+                .iter()
+                .any(|i| i.is_bookmarked || (self.mark != 0 && i.id <= self.mark));
+            illusts.extend(page);
+            if reached || last {
+                complete = true;
+                break;
+            }
+        }
+        let page = Page {
+            illusts,
+            next_url: None,
+        };
+        Ok((page, complete))
+    }
+
+    /// The first page of the feed set by `feed_url` and `restrict`.
+    async fn feed(&self, config: &TrackerConfig) -> Result<Page> {
+        let url = match (&config.feed_url, config.restrict.as_str()) {
+            (Some(url), _) => url.clone(),
+            (None, "public") => return Ok(self.api.illust_follow(Restrict::Public).await?),
+            (None, "private") => return Ok(self.api.illust_follow(Restrict::Private).await?),
+            (None, restrict) => format!(
+                "https://app-api.pixiv.net/v2/illust/follow?restrict={}",
+                restrict
+            ),
+        };
+        Ok(self.api.call_url(&url).await?)
+    }
+
+    /// Any AppAPI endpoint, counted against the API budget.
+    pub async fn call<T: DeserializeOwned>(
+        &mut self,
+        config: &TrackerConfig,
+        url: &str,
+    ) -> Result<T> {
+        timed(config, "auth", self.api.ensure_authed())
+            .await
+            .context(AuthFailed)?;
+        self.record_call();
+        timed(config, "call", self.api.call_url(url)).await
+    }
+
+    /// The first `limit` illusts of an AppAPI list, e.g. a ranking, following `next_url`.
+    pub async fn fetch(
+        &mut self,
+        config: &TrackerConfig,
+        url: &str,
+        limit: usize,
+    ) -> Result<Vec<Illust>> {
+        let mut illusts = Vec::new();
+        let mut next = Some(url.to_owned());
+        while let Some(url) = next.filter(|_| illusts.len() < limit) {
+            let r: Page = self.call(config, &url).await?;
+            illusts.extend(r.illusts);
+            next = r.next_url;
+        }
+        illusts.truncate(limit);
+        Ok(illusts)
+    }
+
+    /// Walks the follow feed up to the anchor, see [`AnchorMode`].
+    pub async fn refresh(&mut self, config: &TrackerConfig) -> Result<FeedDelta> {
+        self.update_tz(config);
+        let before = self.dist();
+        let iid = self.iid;
+        let (r, web) = match timed(config, "auth", self.api.ensure_authed()).await {
+            Ok(()) => {
+                self.record_call();
+                (timed(config, "follow feed", self.feed(config)).await?, None)
+            }
+            Err(e) => {
+                let (r, complete) = self.web_feed(config, e.context(AuthFailed)).await?;
+                (r, Some(complete))
+            }
+        };
+
+        // A quiet feed returns the same first page, so the rest of the scan would change nothing
+        // as long as the anchor is on it; bookmarks further down are only seen by walking there.
+        let head = r.fingerprint();
+        if self.head == Some(head) {
+            debug!("first page unchanged");
+            return Ok(FeedDelta {
+                anchor_changed: false,
+                before,
+                after: before,
+                cached: true,
+                pages: 1,
+                web: web.is_some(),
+            });
+        }
+        self.head = None;
+        let newest = r.illusts.iter().map(|i| i.id).max();
+        let first: Vec<IllustId> = r.illusts.iter().map(|i| i.id).collect();
+        let pages = self.scan(config, r).await?;
+        if web == Some(false) {
+            // The website feed is walked before the scan, which takes its end as the feed's.
+            self.remain = true;
+        }
+        if first.contains(&self.iid) {
+            self.head = Some(head);
+        }
+        self.max_seen = self.max_seen.max(newest.unwrap_or_default());
+        self.full = false;
+        self.escalate(config);
+        self.cache_thumbs(config).await;
+        if self.iid != iid {
+            self.changed_at = Some(OffsetDateTime::now_utc());
+        }
+        Ok(FeedDelta {
+            anchor_changed: self.iid != iid,
+            before,
+            after: self.dist(),
+            cached: false,
+            pages,
+            web: web.is_some(),
+        })
+    }
+
+    fn escalated_pages(&self, config: &TrackerConfig) -> u32 {
+        let pages = config
+            .max_pages
+            .saturating_mul(1 << self.escalation.min(16));
+        config
+            .max_pages_cap
+            .map_or(pages, |cap| pages.min(cap.max(config.max_pages)))
+    }
+
+    /// Walks more pages next time while the anchor is out of reach, giving up at the cap.
+    fn escalate(&mut self, config: &TrackerConfig) {
+        let Some(cap) = config.max_pages_cap else {
+            return;
+        };
+        if !self.remain {
+            self.escalation = 0;
+            self.escalation_exhausted = false;
+        } else if self.metered || self.escalation_exhausted {
+            self.escalation = 0;
+        } else if self.escalated_pages(config) < cap {
+            self.escalation += 1;
+            info!(
+                "anchor not found, trying {} pages next",
+                self.escalated_pages(config)
+            );
+            // Walk past what earlier scans have seen, or the next one stops there.
+            self.head = None;
+            self.full = true;
+        } else {
+            warn!("anchor not found within {} pages, giving up", cap);
+            self.escalation = 0;
+            self.escalation_exhausted = true;
+        }
+    }
+
+    /// Rebuilds the unread list and the visited set from scratch, walking the feed past
+    /// `max_pages` back to the anchor or `days` ago, pausing `pause` between pages.
+    pub async fn backfill(
+        &mut self,
+        config: &TrackerConfig,
+        days: u32,
+        pause: Duration,
+    ) -> Result<FeedDelta> {
+        let cutoff = OffsetDateTime::now_utc()
+            .checked_sub(time::Duration::days(days.into()))
+            .with_context(|| format!("{} days is too far back", days))?;
+        self.vis = Visited::default();
+        self.unread.clear();
+        self.max_seen = 0;
+        self.escalation = 0;
+        self.escalation_exhausted = false;
+        self.invalidate();
+        self.backfill = Some((cutoff, pause));
+        let r = self.refresh(config).await;
+        self.backfill = None;
+        r
+    }
+
+    /// Returns the number of pages walked.
+    async fn scan(&mut self, config: &TrackerConfig, mut r: Page) -> Result<u32> {
+        let max_pages = if self.backfill.is_some() {
+            u32::MAX
+        } else if self.metered || self.throttled(config) {
+            config.max_pages.min(config.metered_max_pages)
+        } else {
+            self.escalated_pages(config)
+        };
+        let mut pn = 1;
+        let mut ids = BTreeSet::new();
+        let mut unread = Vec::new();
+        loop {
+            debug!("page {} has {} illusts", pn, r.illusts.len());
+            let newest = r.illusts.first().map(|i| i.id);
+            let mut may_skip = pn >= config.min_skip_pages;
+            for illust in r.illusts {
+                let reached = match config.anchor {
+                    AnchorMode::Bookmark => illust.is_bookmarked,
+                    AnchorMode::MarkRead => self.mark != 0 && illust.id <= self.mark,
+                    // The feed is newest first, so the first hit is the newer boundary.
+                    AnchorMode::Hybrid => {
+                        illust.is_bookmarked || (self.mark != 0 && illust.id <= self.mark)
+                    }
+                };
+                if reached {
+                    debug!("anchor: {illust:#?}");
+                    if self.iid != illust.id {
+                        debug!("new id: {} time: {}", illust.id, illust.create_date);
+                        self.since = self.convert_date(&illust.create_date)?;
+                        self.iid = illust.id;
+                    }
+                    if self.anchor.as_ref().map(|a| a.id) != Some(illust.id) {
+                        self.anchor = Some(illust.clone());
+                    }
+                    if self.iid != self.image_iid && !self.shown(&illust) {
+                        // Rather no image than the previous anchor's.
+                        let _ = fs::remove_file(IMG_FILE);
+                        self.image_iid = self.iid;
+                    } else if self.iid != self.image_iid && self.downloads() {
+                        let url = illust.image_url(self.image_size);
+                        self.download_to(config, url, Path::new(IMG_FILE)).await?;
+                        if let Some(p) = &config.process {
+                            if let Err(e) = process::apply(p, Path::new(IMG_FILE)) {
+                                warn!("process {}: {:#}", IMG_FILE, e);
+                            }
+                        }
+                        self.image_iid = self.iid;
+                    }
+                    self.remain = false;
+                    self.skip = false;
+                    self.vis = ids.into_iter().collect();
+                    self.unread = unread;
+                    self.opened.remove_up_to(illust.id);
+                    return Ok(pn);
+                }
+                if self.opened.contains(illust.id) {
+                    continue;
+                }
+                ids.insert(illust.id);
+                if may_skip && !self.vis.contains(illust.id) {
+                    may_skip = false;
+                }
+                unread.push(illust);
+            }
+            // The feed is newest first, so everything from here on was counted by earlier scans.
+            let caught_up = newest.is_some_and(|id| id <= self.max_seen);
+            if caught_up && !self.full && self.max_seen != 0 {
+                debug!("caught up with the last scan at page {}", pn);
+                // The pages left out may hold a moved or removed bookmark.
+                self.skip = true;
+                self.merge_unread(unread, &ids);
+                self.vis.extend(ids);
+                return Ok(pn);
+            }
+            if may_skip {
+                if !self.skip {
+                    warn!("skipping from page {}", pn);
+                    self.skip = true;
+                }
+                self.merge_unread(unread, &ids);
+                self.vis.extend(ids.into_iter());
+                return Ok(pn);
+            }
+            if let Some(url) = r.next_url {
+                if self.stop.load(Ordering::Relaxed) {
+                    info!("stopping the scan at page {}", pn);
+                    self.remain = true;
+                } else if pn >= max_pages {
+                    if !self.remain {
+                        warn!("reached max pages {}", pn);
+                        self.remain = true;
+                    }
+                } else if self.backfill_done(&unread)? {
+                    info!("backfilled {} pages", pn);
+                    self.remain = true;
+                } else {
+                    if let Some((_, pause)) = self.backfill {
+                        sleep(self.budget_wait(config).unwrap_or(pause)).await;
+                    }
+                    self.record_call();
+                    r = timed(config, "next page", self.api.call_url(&url)).await?;
+                    pn += 1;
+                    continue;
+                }
+            } else {
+                warn!("no more pages");
+                self.remain = false;
+                self.skip = false;
+            }
+            self.merge_unread(unread, &ids);
+            self.vis.extend(ids.into_iter());
+            return Ok(pn);
+        }
+    }
+
+    async fn download_to(&self, config: &TrackerConfig, url: &str, path: &Path) -> Result<()> {
+        let mut image = timed(config, "download", self.downloader.download(url)).await?;
+        let mut file = fs::File::create(path)?;
+
+        while let Some(chunk) = timed(config, "download", image.chunk()).await? {
+            file.write_all(&chunk)?;
+        }
+        debug!(
+            "downloaded {} bytes to {}",
+            file.stream_position()?,
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Fetches the missing thumbnails of the unread illusts, then trims the cache.
+    async fn cache_thumbs(&self, config: &TrackerConfig) {
+        let Some(thumbs) = self.thumbs.as_ref().filter(|_| self.downloads()) else {
+            return;
+        };
+        if let Err(e) = fs::create_dir_all(&thumbs.dir) {
+            warn!("thumbs: {}", e);
+            return;
+        }
+        // Oldest first, so the newest unread ones end up the most recently used.
+        for illust in self.unread.iter().rev().filter(|i| self.shown(i)) {
+            let path = thumbs.path(illust.id);
+            if thumbs::touch(&path) {
+                // After the original, or the blurred copy is made again.
+                thumbs::touch(&process::blur_path(&path));
+                continue;
+            }
+            let url = &illust.image_urls.square_medium;
+            if let Err(e) = self.download_to(config, url, &path).await {
+                warn!("thumb {}: {:#}", illust.id, e);
+                let _ = fs::remove_file(&path);
+            }
+        }
+        if let Err(e) = thumbs::evict(thumbs) {
+            warn!("thumbs: {:#}", e);
+        }
+    }
+
+    /// Whether `illust` may be shown or downloaded, it is counted either way.
+    pub fn shown(&self, illust: &Illust) -> bool {
+        let max = match (self.max_sanity_level, self.sfw_only) {
+            (Some(n), true) => n.min(2),
+            (Some(n), false) => n,
+            (None, true) => 2,
+            (None, false) => return true,
+        };
+        illust.sanity_level <= max && !(self.sfw_only && illust.x_restrict > 0)
+    }
+
+    /// Whether the images of `illust` are blurred, see [`TrackerConfig::blur_sanity_level`].
+    pub fn blurred(&self, illust: &Illust) -> bool {
+        self.blur_sanity_level
+            .is_some_and(|n| illust.sanity_level > n)
+    }
+
+    /// The newest unread illust that may be shown.
+    pub fn newest_shown(&self) -> Option<&Illust> {
+        self.unread.iter().find(|i| self.shown(i))
+    }
+
+    /// The bound on a single request, for the side services to follow as well.
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Bounds a request of a side service like the feed's, see [`TrackerConfig::request_timeout`].
+    pub async fn timed<T, E: Into<anyhow::Error>>(
+        &self,
+        what: &str,
+        f: impl Future<Output = Result<T, E>>,
+    ) -> Result<T> {
+        bounded(self.request_timeout, what, f).await
+    }
+
+    /// Downloads an image into memory, each chunk bounded like the feed's requests.
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let mut image = self
+            .timed("download", self.downloader.download(url))
+            .await?;
+        let mut data = Vec::new();
+        while let Some(chunk) = self.timed("download", image.chunk()).await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
+    pub fn image_size(&self) -> ImageSize {
+        self.image_size
+    }
+
+    /// The cached thumbnail of `id`, see [`TrackerConfig::thumbs`].
+    pub fn thumb(&self, id: IllustId) -> Option<PathBuf> {
+        let path = self.thumbs.as_ref()?.path(id);
+        path.exists().then_some(path)
+    }
+
+    /// Whether a backfill has walked back past its cutoff date.
+    fn backfill_done(&self, unread: &[Illust]) -> Result<bool> {
+        let (Some((cutoff, _)), Some(oldest)) = (self.backfill, unread.last()) else {
+            return Ok(false);
+        };
+        Ok(self.convert_date(&oldest.create_date)? < cutoff)
+    }
+
+    /// Puts the newly scanned illusts in front of the unread ones kept from earlier refreshes.
+    fn merge_unread(&mut self, mut unread: Vec<Illust>, ids: &BTreeSet<IllustId>) {
+        unread.extend(self.unread.drain(..).filter(|i| !ids.contains(&i.id)));
+        self.unread = unread;
+    }
+
+    /// The unread count, of the `window_hours` if `count_window` is set.
+    pub fn dist(&self) -> usize {
+        match self.window_count() {
+            Some(n) if self.count_window => n,
+            _ => self.vis.len(),
+        }
+    }
+
+    /// Unread illusts posted in the last `window_hours`.
+    pub fn window_count(&self) -> Option<usize> {
+        let hours = self.window_hours?;
+        let cutoff = OffsetDateTime::now_utc() - time::Duration::hours(hours.into());
+        let posted = |i: &&Illust| {
+            OffsetDateTime::parse(
+                &i.create_date,
+                &format_description::well_known::Iso8601::DEFAULT,
+            )
+            .is_ok_and(|t| t >= cutoff)
+        };
+        Some(self.unread.iter().filter(posted).count())
+    }
+
+    pub fn severity(&self) -> Severity {
+        match (self.remain, self.skip) {
+            (false, false) => Severity::Exact,
+            (false, true) => Severity::Approximate,
+            (true, false) => Severity::LowerBound,
+            (true, true) => Severity::Unreliable,
+        }
+    }
+
+    /// The unread count marked with its [`Severity`], e.g. `~42` or `300+`.
+    pub fn dist_text(&self) -> String {
+        match self.severity() {
+            Severity::Exact => self.dist().to_string(),
+            Severity::Approximate => format!("~{}", self.dist()),
+            Severity::LowerBound | Severity::Unreliable => format!("{}+", self.dist()),
+        }
+    }
+
+    /// Changes whenever the anchor or the count does.
+    pub fn token(&self) -> (IllustId, usize) {
+        (self.iid, self.dist())
+    }
+}
@@ -1,264 +1,611 @@
 #[macro_use]
 extern crate log;
 
-use std::collections::BTreeSet;
-use std::io::{Seek, Write};
-use std::ops::{Deref, DerefMut};
-use std::process::Command;
-use std::sync::Arc;
-use std::{env, fs};
-
-use anyhow::{bail, Result};
-use futures::{FutureExt, StreamExt};
-use inotify::{Inotify, WatchMask};
-use pixiv::aapi::Restrict;
-use pixiv::client::{AuthedClient, AuthedState};
-use pixiv::download::DownloadClient;
-use pixiv::model::IllustId;
-use serde::{Deserialize, Serialize};
-use time::{format_description, macros::format_description, OffsetDateTime, UtcOffset};
-use tokio::sync::Notify;
-use tokio::time::{sleep, Duration};
+mod apprise;
+mod badge;
+mod browse;
+mod callback;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod crash;
+mod digest;
+mod doctor;
+mod eventlog;
+mod export;
+mod fanbox;
+mod feeds;
+mod history;
+#[cfg(feature = "hotkey")]
+mod hotkey;
+mod influx;
+mod init;
+mod instance;
+mod launcher;
+mod matrix;
+mod notifier;
+mod otel;
+mod paths;
+mod pixivision;
+mod power;
+#[cfg(feature = "qr")]
+mod qr;
+mod ranking;
+mod rules;
+mod schedule;
+mod search;
+mod secrets;
+mod server;
+mod statusbar;
+mod sync;
+mod template;
+mod termux;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "update")]
+mod update;
+mod validate;
+mod wake;
+#[cfg(feature = "wallpaper")]
+mod wallpaper;
+mod watchdog;
+mod ws;
+
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::atomic::Ordering;
+use std::{env, fmt, fs, io, process};
+
+use anyhow::{anyhow, bail, Context, Result};
+use callback::{CallbackConfig, Event, Payload};
+use clap::{CommandFactory, Parser, Subcommand};
+use futures::FutureExt;
+use illust_notify::{
+    trace, AnchorMode, AuthFailed, Illust, Outage, Tracker, TrackerConfig, TrackerDump,
+};
+use notifier::Notifier;
+use rules::{Action, Rules};
+use serde::Deserialize;
+use server::{HttpConfig, Shared, Status};
+use statusbar::StatusbarConfig;
+use tokio::sync::watch;
+use tokio::time::{sleep_until, timeout, Duration, Instant};
+use wake::Wake;
+use watchdog::Watchdog;
 
 fn default_delay() -> u32 {
     300
 }
 
-fn default_max_pages() -> u32 {
-    5
+fn default_error_threshold() -> u32 {
+    3
 }
 
-fn default_min_skip_pages() -> u32 {
+fn default_outage_threshold() -> u32 {
     3
 }
 
-const CONFIG_FILE: &str = "config.json";
-const CALLBACK_FILE: &str = "./callback";
-const NOTIFY_FILE: &str = "notify";
-const IMG_FILE: &str = "img.jpg";
-const STATE_FILE: &str = "state.json";
-
-#[derive(Deserialize, Debug, Clone)]
-struct Config {
-    refresh_token: String,
-    #[serde(default = "default_delay")]
-    delay: u32,
-    #[serde(default = "default_max_pages")]
-    max_pages: u32,
-    #[serde(default = "default_min_skip_pages")]
-    min_skip_pages: u32,
+fn default_refresh_deadline() -> u32 {
+    120
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct ImageUrls {
-    square_medium: String,
+fn default_offline_probe() -> u32 {
+    30
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct Illust {
-    id: IllustId,
-    title: String,
-    create_date: String,
-    is_bookmarked: bool,
-    image_urls: ImageUrls,
+fn default_outage_delay() -> u32 {
+    1800
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct Page {
-    illusts: Vec<Illust>,
-    next_url: Option<String>,
+fn default_gap_report_hours() -> u32 {
+    6
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct AppState {
-    iid: IllustId,
-    since: OffsetDateTime,
-    remain: bool,
-    skip: bool,
-    vis: BTreeSet<IllustId>,
+fn default_ranking_interval() -> u64 {
+    3600
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        Self {
-            iid: 0,
-            since: OffsetDateTime::UNIX_EPOCH,
-            remain: false,
-            skip: false,
-            vis: BTreeSet::new(),
-        }
-    }
+fn default_search_interval() -> u64 {
+    1800
 }
 
-#[derive(Deserialize, Serialize)]
-struct AppDump {
-    api: AuthedState,
+const NOTIFY_FILE: &str = "notify";
+const TRIGGERS_DIR: &str = "triggers";
+const STATE_FILE: &str = "state.json";
+const PROBE_ADDR: &str = "app-api.pixiv.net:443";
+
+#[derive(Deserialize, Debug, Clone)]
+struct Config {
+    /// Inline, `"keyring"`, or read from a file or command, see [`secrets`]; may be left out
+    /// with `keyring_account`.
+    #[serde(default, deserialize_with = "secrets::deserialize")]
+    refresh_token: String,
+    /// Seconds between refreshes of the follow feed; the other feeds keep their own intervals,
+    /// see [`feeds`].
+    #[serde(default = "default_delay")]
+    delay: u32,
+    /// Cron expression to refresh at instead of every `delay`, see [`schedule`]; the other feeds
+    /// are then only polled along with the refreshes.
+    #[serde(default, deserialize_with = "schedule::deserialize")]
+    schedule: Option<schedule::Schedule>,
+    #[serde(default)]
+    notify_cooldown: u32,
+    #[serde(default)]
+    notify_threshold: usize,
+    notify_reminder: Option<usize>,
+    statusbar: Option<StatusbarConfig>,
+    #[serde(default)]
+    callback_interface: callback::Interface,
+    callbacks: Option<Vec<CallbackConfig>>,
+    /// Consecutive failed refreshes before an `error` event, 0 to never send one.
+    #[serde(default = "default_error_threshold")]
+    error_threshold: u32,
+    /// Seconds a whole refresh may take, however many pages it walks.
+    #[serde(default = "default_refresh_deadline")]
+    refresh_deadline: u32,
+    /// Seconds between connectivity checks while offline.
+    #[serde(default = "default_offline_probe")]
+    offline_probe: u32,
+    /// Seconds between refreshes while pixiv is under maintenance or keeps returning 5xx.
+    #[serde(default = "default_outage_delay")]
+    outage_delay: u32,
+    /// Consecutive 5xx responses taken for an outage; a maintenance page counts at once.
+    #[serde(default = "default_outage_threshold")]
+    outage_threshold: u32,
+    /// Report once when the anchor stays beyond `max_pages` this long, 0 to never.
+    #[serde(default = "default_gap_report_hours")]
+    gap_report_hours: u32,
+    /// Delay in the low-bandwidth mode, three times `delay` by default.
+    metered_delay: Option<u32>,
+    /// Delay while on battery, which also pauses image downloads; the power state is only
+    /// checked with this set.
+    delay_on_battery: Option<u32>,
+    /// Randomly lengthens or shortens each wait by up to this percentage, so instances started
+    /// together drift apart.
+    #[serde(default)]
+    delay_jitter_pct: u32,
+    http: Option<HttpConfig>,
+    matrix: Option<matrix::MatrixConfig>,
+    termux: Option<termux::TermuxConfig>,
+    launcher: Option<launcher::LauncherConfig>,
+    #[cfg(feature = "wallpaper")]
+    wallpaper: Option<wallpaper::WallpaperConfig>,
+    /// Global hotkeys, read at startup only.
+    #[cfg(feature = "hotkey")]
+    hotkey: Option<hotkey::HotkeyConfig>,
+    /// Copies the newest unread illust when the count leaves zero.
+    #[cfg(feature = "clipboard")]
+    clipboard: Option<clipboard::ClipboardConfig>,
+    /// A QR code of the newest unread illust, in the log or `qr.png`.
+    #[cfg(feature = "qr")]
+    qr: Option<qr::QrConfig>,
+    /// Rankings watched for followed artists and tags.
+    #[serde(default)]
+    rankings: Vec<ranking::RankingConfig>,
+    /// Seconds between polls of `rankings`.
+    #[serde(default = "default_ranking_interval")]
+    ranking_interval: u64,
+    /// Tag searches followed as extra feeds.
+    #[serde(default)]
+    searches: Vec<search::SearchConfig>,
+    /// Seconds between polls of `searches`.
+    #[serde(default = "default_search_interval")]
+    search_interval: u64,
+    digest: Option<digest::DigestConfig>,
+    /// Append every count transition to a JSONL file.
+    event_log: Option<eventlog::EventLogConfig>,
+    pixivision: Option<pixivision::PixivisionConfig>,
+    fanbox: Option<fanbox::FanboxConfig>,
+    /// Push refresh stats to an InfluxDB-compatible endpoint.
+    influx: Option<influx::InfluxConfig>,
+    /// Export each refresh cycle as a trace to an OTLP/HTTP collector.
+    otlp: Option<otel::OtlpConfig>,
+    /// Share the anchor with other machines through a WebDAV server.
+    sync: Option<sync::SyncConfig>,
+    /// How new illusts from specific artists are announced, the first matching rule wins.
+    #[serde(default)]
+    rules: Vec<rules::Rule>,
+    #[serde(default)]
+    default_action: Action,
+    /// Regexes matched against the titles, captions and tags of new illusts; a match is
+    /// announced as a `keyword` event even while snoozed or below the threshold.
+    #[serde(default)]
+    alert_keywords: Vec<String>,
+    /// Abort when the watchdog finds the loop stuck, so a supervisor can restart the daemon.
+    #[serde(default)]
+    watchdog_abort: bool,
+    /// Keep the authed session of state.json in the OS keyring, see [`secrets`].
+    #[serde(default)]
+    keyring: bool,
+    /// Keep the refresh token and the session in the keyring under this pixiv account; implies
+    /// `keyring`.
+    keyring_account: Option<String>,
+    /// Drop illusts opened from the TUI or `POST /open` from the unread list and the count.
+    #[serde(default)]
+    dismiss_opened: bool,
+    /// Apprise-style URLs: `matrix://`, `tgram://`, `discord://`, `ntfy://` or `json://`.
+    #[serde(default)]
+    notify_urls: Vec<String>,
     #[serde(flatten)]
-    state: AppState,
+    tracker: TrackerConfig,
 }
 
-struct App {
-    api: AuthedClient,
-    state: AppState,
-    downloader: DownloadClient,
-    tz: UtcOffset,
-    ago: timeago::Formatter,
+impl Config {
+    fn callbacks(&self) -> Vec<CallbackConfig> {
+        match &self.callbacks {
+            Some(callbacks) => callbacks.clone(),
+            None => vec![CallbackConfig::legacy(self.callback_interface)],
+        }
+    }
 }
 
-impl Deref for App {
-    type Target = AppState;
+/// Stands in for refresh errors while the network is unreachable.
+#[derive(Debug)]
+struct Offline;
 
-    fn deref(&self) -> &Self::Target {
-        &self.state
+impl fmt::Display for Offline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("offline")
     }
 }
 
-impl DerefMut for App {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.state
+/// `d` scaled by a random factor within `1 ± pct / 100`.
+fn jitter(d: Duration, pct: u32) -> Duration {
+    if pct == 0 {
+        return d;
     }
+    // A freshly seeded std hasher is random enough and saves a dependency.
+    let r = RandomState::new().build_hasher().finish();
+    let pct = pct.min(100) as f64 / 100.0;
+    let factor = 1.0 + pct * ((r as f64 / u64::MAX as f64) * 2.0 - 1.0);
+    d.mul_f64(factor)
+}
+
+/// Whether the API host can be resolved and connected to.
+async fn online() -> bool {
+    let connect = tokio::net::TcpStream::connect(PROBE_ADDR);
+    matches!(timeout(Duration::from_secs(5), connect).await, Ok(Ok(_)))
 }
 
-const DATE_FORMAT: &[format_description::FormatItem<'static>] =
-    format_description!("[month padding:none]/[day padding:none] [hour padding:none]:[minute]");
-
-impl App {
-    async fn new(refresh_token: &str) -> Result<Self> {
-        Ok(Self {
-            api: AuthedClient::new(refresh_token).await?,
-            state: Default::default(),
-            downloader: DownloadClient::new(),
-            tz: UtcOffset::current_local_offset()?,
-            ago: timeago::Formatter::new(),
-        })
+async fn fire(notifiers: &mut [Box<dyn Notifier>], app: &Tracker, event: Event, message: String) {
+    let payload = Payload {
+        message: Some(message),
+        ..Payload::new(app, event)
+    };
+    notifier::dispatch(notifiers, app, &payload).await;
+}
+
+async fn fire_keywords(
+    notifiers: &mut [Box<dyn Notifier>],
+    app: &Tracker,
+    keywords: Vec<(&Illust, &str)>,
+) {
+    for (illust, keyword) in keywords.into_iter().filter(|(i, _)| app.shown(i)) {
+        let msg = format!(
+            "{} by {} matched {} (https://www.pixiv.net/artworks/{})",
+            illust.title, illust.user.name, keyword, illust.id
+        );
+        info!("keyword: {}", msg);
+        fire(notifiers, app, Event::Keyword, msg).await;
     }
+}
 
-    fn load(dump: AppDump) -> Result<Self> {
-        Ok(Self {
-            api: AuthedClient::load(dump.api),
-            state: dump.state,
-            downloader: DownloadClient::new(),
-            tz: UtcOffset::current_local_offset()?,
-            ago: timeago::Formatter::new(),
-        })
+/// Exit status of `--once` when the anchor or count changed.
+const EXIT_CHANGED: i32 = 10;
+
+/// A single refresh for cron and timers, nothing is throttled across runs.
+async fn run_once(
+    mut app: Tracker,
+    config: &Config,
+    notifiers: &mut [Box<dyn Notifier>],
+    rules: &mut Rules,
+) -> Result<()> {
+    let token = app.token();
+    let deadline = Duration::from_secs(config.refresh_deadline.into());
+    let r = match timeout(deadline, app.refresh(&config.tracker)).await {
+        Ok(r) => r,
+        Err(_) => Err(anyhow!("refresh exceeded {}s", config.refresh_deadline)),
+    };
+    if let Err(e) = r {
+        let event = if Outage::detect(&e).is_some() {
+            Event::Outage
+        } else if e.downcast_ref::<AuthFailed>().is_some() {
+            Event::ReauthNeeded
+        } else {
+            Event::Error
+        };
+        fire(notifiers, &app, event, format!("{:#}", e)).await;
+        if let Some(bar) = &config.statusbar {
+            if let Err(e) = bar.error(&e) {
+                error!("statusbar: {:#?}", e);
+            }
+        }
+        save_state(app, config)?;
+        return Err(e);
     }
 
-    fn dump(self) -> AppDump {
-        AppDump {
-            api: self.api.state,
-            state: self.state,
+    if let Some(bar) = &config.statusbar {
+        if let Err(e) = bar.update(&app) {
+            error!("statusbar: {:#?}", e);
         }
     }
+    let verdict = rules.classify(&app.unread);
+    let action = verdict.action;
+    fire_keywords(notifiers, &app, verdict.keywords).await;
 
-    fn convert_date(&self, date: &str) -> Result<OffsetDateTime> {
-        let t = OffsetDateTime::parse(date, &format_description::well_known::Iso8601::DEFAULT)?
-            .to_offset(self.tz);
-        Ok(t)
+    let changed = token != app.token();
+    info!("{}", app.summary());
+    let reached = app.dist() >= config.notify_threshold;
+    let push = action == Some(Action::Push);
+    if changed && action != Some(Action::Quiet) && (reached || push) {
+        let payload = Payload::new(&app, Event::CountChanged);
+        notifier::dispatch(notifiers, &app, &payload).await;
     }
+    if reached {
+        let payload = Payload::new(&app, Event::Refresh);
+        notifier::dispatch(notifiers, &app, &payload).await;
+    }
+    save_state(app, config)?;
+    if changed {
+        process::exit(EXIT_CHANGED);
+    }
+    Ok(())
+}
 
-    fn since(&self) -> String {
-        match self.since.format(&DATE_FORMAT) {
-            Ok(s) => s,
-            Err(e) => format!("Error: {:?}", e),
+fn load_config() -> Result<Config> {
+    let path = paths::config_file();
+    let text = fs::read_to_string(&path).with_context(|| format!("{}", path.display()))?;
+    let mut config = validate::parse(&text).with_context(|| format!("{}", path.display()))?;
+    if paths::portable() {
+        check_portable(&config)?;
+    }
+    secrets::set_account(config.keyring_account.as_deref());
+    if config.keyring_account.is_some() {
+        config.keyring = true;
+        if config.refresh_token.is_empty() {
+            config.refresh_token = secrets::MARKER.to_owned();
         }
     }
+    if config.refresh_token.is_empty() {
+        bail!("missing refresh_token");
+    }
+    config.refresh_token = secrets::resolve(&config.refresh_token, secrets::REFRESH_TOKEN)?;
+    if let Some(s) = &config.tracker.web_session {
+        config.tracker.web_session = Some(secrets::resolve(s, secrets::WEB_SESSION)?);
+    }
+    Ok(config)
+}
 
-    fn since_ago(&self) -> String {
-        let now = OffsetDateTime::now_utc().to_offset(self.tz);
-        let d = now - self.since;
-        self.ago.convert(d.unsigned_abs())
+/// Everything the daemon reads or writes has to stay inside the portable directory.
+fn check_portable(config: &Config) -> Result<()> {
+    let keyring = config.keyring
+        || config.keyring_account.is_some()
+        || config.refresh_token == secrets::MARKER
+        || config.tracker.web_session.as_deref() == Some(secrets::MARKER);
+    if keyring {
+        bail!("the keyring stays on this machine, keep the secrets in config.json with --portable");
+    }
+    let mut files = Vec::new();
+    if let Some(t) = &config.tracker.thumbs {
+        files.push(("tracker.thumbs.dir", t.dir.clone()));
+    }
+    if let Some(log) = &config.event_log {
+        files.push(("event_log.path", log.path.clone()));
+    }
+    if let Some(bar) = config.statusbar.as_ref().filter(|b| b.path() != "-") {
+        files.push(("statusbar.path", PathBuf::from(bar.path())));
+    }
+    if let Some(file) = config.digest.as_ref().and_then(|d| d.file.clone()) {
+        files.push(("digest.file", file));
+    }
+    for c in config.callbacks() {
+        files.push(("callbacks.path", PathBuf::from(c.path)));
     }
+    for (what, path) in files {
+        paths::check_portable(what, &path)?;
+    }
+    Ok(())
+}
 
-    async fn refresh(&mut self, config: &Config) -> Result<()> {
-        self.api.ensure_authed().await?;
-        let mut r: Page = self.api.illust_follow(Restrict::Public).await?;
+fn load_state(path: &str, config: &Config) -> Result<Tracker> {
+    let mut v = serde_json::from_str(&fs::read_to_string(path)?)?;
+    secrets::unseal_state(&mut v)?;
+    Tracker::load(TrackerDump::parse(v)?, &config.tracker)
+}
 
-        let mut pn = 1;
-        let mut ids = BTreeSet::new();
-        loop {
-            debug!("page {} has {} illusts", pn, r.illusts.len());
-            let mut may_skip = pn >= config.min_skip_pages;
-            for illust in r.illusts {
-                if illust.is_bookmarked {
-                    debug!("bookmarked: {illust:#?}");
-                    if self.iid != illust.id {
-                        debug!("new id: {} time: {}", illust.id, illust.create_date);
-                        self.since = self.convert_date(&illust.create_date)?;
-
-                        let mut image = self
-                            .downloader
-                            .download(&illust.image_urls.square_medium)
-                            .await?;
-                        let mut file = fs::File::create(IMG_FILE)?;
-
-                        while let Some(chunk) = image.chunk().await? {
-                            file.write_all(&chunk)?;
-                        }
-                        debug!("downloaded {} bytes", file.stream_position()?);
+fn save_state(app: Tracker, config: &Config) -> Result<()> {
+    let mut v = serde_json::to_value(app.dump())?;
+    if config.keyring {
+        secrets::seal_state(&mut v)?;
+    }
+    fs::write(STATE_FILE, serde_json::to_string_pretty(&v)?)?;
+    Ok(())
+}
 
-                        self.iid = illust.id;
-                    }
-                    self.remain = false;
-                    self.skip = false;
-                    self.vis = ids;
-                    return Ok(());
-                }
-                ids.insert(illust.id);
-                if may_skip && !self.vis.contains(&illust.id) {
-                    may_skip = false;
-                }
-            }
-            if may_skip {
-                if !self.skip {
-                    warn!("skipping from page {}", pn);
-                    self.skip = true;
-                }
-                self.vis.extend(ids.into_iter());
-                return Ok(());
-            }
-            if let Some(url) = r.next_url {
-                if pn >= config.max_pages {
-                    if !self.remain {
-                        warn!("reached max pages {}", pn);
-                        self.remain = true;
-                    }
-                } else {
-                    r = self.api.call_url(&url).await?;
-                    pn += 1;
-                    continue;
-                }
-            } else {
-                warn!("no more pages");
-                self.remain = false;
-                self.skip = false;
-            }
-            self.vis.extend(ids.into_iter());
-            return Ok(());
+/// Moves plaintext secrets of config.json and state.json into the keyring.
+fn migrate_secrets() -> Result<()> {
+    let path = paths::config_file();
+    let mut config: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    let Some(map) = config.as_object_mut() else {
+        bail!("{} is not an object", path.display());
+    };
+    secrets::set_account(map.get("keyring_account").and_then(|a| a.as_str()));
+    match map.get_mut("refresh_token") {
+        Some(token) if token.is_string() && *token != secrets::MARKER => {
+            secrets::set(
+                secrets::REFRESH_TOKEN,
+                token.as_str().context("refresh_token")?,
+            )?;
+            *token = secrets::MARKER.into();
+            println!("moved refresh_token");
         }
+        _ => {}
     }
+    map.insert("keyring".to_owned(), true.into());
 
-    fn dist(&self) -> usize {
-        self.vis.len()
+    match fs::read_to_string(STATE_FILE) {
+        Ok(s) => {
+            let mut state = serde_json::from_str(&s)?;
+            secrets::seal_state(&mut state)?;
+            fs::write(STATE_FILE, serde_json::to_string_pretty(&state)?)?;
+            println!("moved the session of {}", STATE_FILE);
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
     }
+    fs::write(&path, serde_json::to_string_pretty(&config)?)?;
+    println!(
+        "updated {}, reload or restart a running daemon",
+        path.display()
+    );
+    Ok(())
+}
 
-    fn token(&self) -> (IllustId, usize) {
-        (self.iid, self.dist())
-    }
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Directory holding config.json and the state files, instead of the platform directories
+    dir: Option<PathBuf>,
+    /// Like `dir`, created if missing, and refusing config entries that point outside it or to
+    /// the keyring, so the directory can be moved between machines
+    #[arg(long, value_name = "DIR", conflicts_with = "dir")]
+    portable: Option<PathBuf>,
+    /// Ask an instance already running in the same directory to shut down first
+    #[arg(long)]
+    takeover: bool,
+    /// Refresh once, notify and save the state, then exit with 10 if the count changed
+    #[arg(long)]
+    once: bool,
+    #[command(subcommand)]
+    command: Option<Cmd>,
 }
 
-fn notify(bin: &str, args: &[&str]) -> Result<()> {
-    debug!("notify: {} {:?}", bin, args);
-    let r = Command::new(bin).args(args).spawn()?.wait()?;
-    if r.success() {
-        debug!("notify: returned {:?}", r.code());
-    } else {
-        bail!("returned {:?}", r.code());
-    }
-    Ok(())
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Check the config, credentials, callbacks and file permissions
+    Doctor,
+    /// Print a Home Assistant RESTful sensor config for the HTTP endpoint
+    Hass {
+        /// Host name Home Assistant uses to reach the daemon
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// Suppress notifications of the running daemon for a while, 0 to resume
+    Snooze {
+        #[arg(default_value_t = 60)]
+        minutes: u64,
+    },
+    /// Make the running daemon re-read config.json
+    Reload,
+    /// Switch the running daemon to the low-bandwidth mode
+    Metered {
+        /// Switch back to the normal mode instead
+        #[arg(long)]
+        off: bool,
+    },
+    /// Zero the count of the running daemon, in the mark_read and hybrid anchor modes
+    MarkRead,
+    /// Dump the unread illusts, from the running daemon if reachable, else from state.json
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: export::Format,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the count of the running daemon, or of state.json if it is not reachable
+    Status {
+        /// Print the full status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Walk the follow feed back past max_pages once to rebuild the unread list after a long
+    /// absence, stopping at the anchor or after `days`
+    Backfill {
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+        /// Seconds to wait between pages
+        #[arg(long, default_value_t = 2)]
+        pause: u64,
+    },
+    /// Print the count over the last days from the event log, without calling the API
+    History {
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+    /// Replace this binary with the latest release after verifying its checksum
+    #[cfg(feature = "update")]
+    Update {
+        /// Only report a newer release, also through the notification backends
+        #[arg(long)]
+        check: bool,
+    },
+    /// Run the daemon in the foreground with the count, the unread illusts and the log on screen
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Write a first config.json by answering a few questions, then check it
+    Init {
+        /// Replace an existing config.json and state.json
+        #[arg(long)]
+        force: bool,
+    },
+    /// Manage state.json
+    State {
+        #[command(subcommand)]
+        command: StateCmd,
+    },
+    /// Manage secrets kept in the OS keyring
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCmd,
+    },
+    /// Print a completion script, e.g. `illust-notify completions zsh > _illust-notify`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, or write one for each subcommand into a directory
+    Man {
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretsCmd {
+    /// Move the refresh token and the authed session into the keyring
+    Migrate,
 }
 
-fn load_state(path: &str) -> Result<App> {
-    App::load(serde_json::from_str(&fs::read_to_string(path)?)?)
+#[derive(Subcommand, Debug)]
+enum StateCmd {
+    /// Create state.json anchored at your newest bookmark instead of an empty one
+    Init {
+        /// Your pixiv user id
+        user: u64,
+        /// Use the newest private bookmark
+        #[arg(long)]
+        private: bool,
+        /// Replace an existing state.json, discarding its unread set
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// The man pages, from the same definitions as `--help`.
+fn man(dir: Option<&Path>) -> Result<()> {
+    let cmd = Cli::command();
+    match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(cmd, dir)?;
+        }
+        None => clap_mangen::Man::new(cmd).render(&mut io::stdout())?,
+    }
+    Ok(())
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -266,96 +613,749 @@ async fn main() -> Result<()> {
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "info");
     }
+
+    let cli = Cli::parse();
+    #[cfg(feature = "tui")]
+    let log = match cli.command {
+        Some(Cmd::Tui) => Some(tui::capture()?),
+        _ => {
+            pretty_env_logger::init_timed();
+            None
+        }
+    };
+    #[cfg(not(feature = "tui"))]
     pretty_env_logger::init_timed();
+    // For packaging, which should not touch the data directories.
+    match &cli.command {
+        Some(Cmd::Completions { shell }) => {
+            let mut cmd = Cli::command();
+            let name = cmd.get_name().to_owned();
+            clap_complete::generate(*shell, &mut cmd, name, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Cmd::Man { dir }) => return man(dir.as_deref()),
+        _ => {}
+    }
+    paths::init(cli.dir.as_deref(), cli.portable.as_deref())?;
 
-    if let Some(dir) = env::args().nth(1) {
-        env::set_current_dir(&dir)?;
+    match cli.command {
+        Some(Cmd::Doctor) => return doctor::run().await,
+        Some(Cmd::Init { force }) => return init::run(force).await,
+        Some(Cmd::Hass { host }) => {
+            let config = load_config()?;
+            let Some(http) = &config.http else {
+                bail!("http.listen is not configured");
+            };
+            print!("{}", server::hass_yaml(http, host.as_deref(), config.delay));
+            return Ok(());
+        }
+        Some(Cmd::MarkRead) => {
+            let config = load_config()?;
+            let Some(http) = &config.http else {
+                bail!("http.listen is not configured");
+            };
+            print!("{}", server::post(http, "/mark-read")?);
+            return Ok(());
+        }
+        Some(Cmd::Reload) => {
+            let config = load_config()?;
+            let Some(http) = &config.http else {
+                bail!("http.listen is not configured");
+            };
+            print!("{}", server::post(http, "/reload")?);
+            return Ok(());
+        }
+        Some(Cmd::Metered { off }) => {
+            let config = load_config()?;
+            let Some(http) = &config.http else {
+                bail!("http.listen is not configured");
+            };
+            let path = format!("/metered?on={}", if off { 0 } else { 1 });
+            print!("{}", server::post(http, &path)?);
+            return Ok(());
+        }
+        Some(Cmd::Export { format, output }) => {
+            let config = load_config()?;
+            let path = format!("/export?format={}", format.as_str());
+            let daemon = config.http.as_ref().map(|http| server::get(http, &path));
+            let text = match daemon {
+                Some(Ok(text)) => text,
+                r => {
+                    if let Some(Err(e)) = r {
+                        warn!("daemon unreachable, reading {}: {:#}", STATE_FILE, e);
+                    }
+                    let backlog: export::Backlog =
+                        serde_json::from_str(&fs::read_to_string(STATE_FILE)?)?;
+                    export::render(&backlog.unread, format)?
+                }
+            };
+            match output {
+                Some(path) => fs::write(path, text)?,
+                None => print!("{}", text),
+            }
+            return Ok(());
+        }
+        Some(Cmd::Status { json }) => {
+            let config = load_config()?;
+            let daemon = config
+                .http
+                .as_ref()
+                .map(|http| server::get(http, "/status"));
+            let status = match daemon {
+                Some(Ok(body)) => serde_json::from_str(&body)?,
+                r => {
+                    if let Some(Err(e)) = r {
+                        debug!("daemon unreachable, reading {}: {:#}", STATE_FILE, e);
+                    }
+                    Status::new(&load_state(STATE_FILE, &config)?)
+                }
+            };
+            if json {
+                println!("{}", serde_json::to_string(&status)?);
+            } else {
+                println!("{}", status.line());
+            }
+            return Ok(());
+        }
+        Some(Cmd::State {
+            command:
+                StateCmd::Init {
+                    user,
+                    private,
+                    force,
+                },
+        }) => {
+            if !force && fs::metadata(STATE_FILE).is_ok() {
+                bail!("{} exists, pass --force to replace it", STATE_FILE);
+            }
+            let config = load_config()?;
+            let mut app = Tracker::new(&config.refresh_token, &config.tracker).await?;
+            app.seed(&config.tracker, user, private).await?;
+            println!("anchored at {} since {}", app.iid, app.since());
+            save_state(app, &config)?;
+            return Ok(());
+        }
+        Some(Cmd::Backfill { days, pause }) => {
+            let config = load_config()?;
+            let _lock = instance::lock(false, None).await?;
+            let mut app = match load_state(STATE_FILE, &config) {
+                Ok(app) => app,
+                Err(e) => {
+                    warn!("load state: {:#?}", e);
+                    Tracker::new(&config.refresh_token, &config.tracker).await?
+                }
+            };
+            let delta = app
+                .backfill(&config.tracker, days, Duration::from_secs(pause))
+                .await?;
+            println!("{} unread in {} pages", app.unread.len(), delta.pages);
+            save_state(app, &config)?;
+            return Ok(());
+        }
+        Some(Cmd::History { days }) => {
+            let config = load_config()?;
+            let log = config.event_log.clone().unwrap_or_default();
+            let tz = illust_notify::resolve_tz(config.tracker.timezone.as_deref());
+            return history::print(&log, days, tz);
+        }
+        #[cfg(feature = "update")]
+        Some(Cmd::Update { check }) => {
+            let updater = update::Updater::new();
+            let release = updater.latest().await?;
+            if !release.newer() {
+                println!("{} is up to date", env!("CARGO_PKG_VERSION"));
+            } else if check {
+                let msg = format!("{} is available: {}", release.tag_name, release.html_url);
+                println!("{}", msg);
+                let config = load_config()?;
+                let app = load_state(STATE_FILE, &config)?;
+                let mut notifiers = notifier::registry(&config);
+                fire(&mut notifiers, &app, Event::Update, msg).await;
+            } else {
+                updater.install(&release).await?;
+                println!("updated to {}, restart to run it", release.tag_name);
+            }
+            return Ok(());
+        }
+        Some(Cmd::Secrets {
+            command: SecretsCmd::Migrate,
+        }) => return migrate_secrets(),
+        Some(Cmd::Snooze { minutes }) => {
+            let config = load_config()?;
+            let Some(http) = &config.http else {
+                bail!("http.listen is not configured");
+            };
+            let path = format!("/snooze?minutes={}", minutes);
+            print!("{}", server::post(http, &path)?);
+            return Ok(());
+        }
+        #[cfg(feature = "tui")]
+        Some(Cmd::Tui) => {}
+        Some(Cmd::Completions { .. } | Cmd::Man { .. }) => unreachable!(),
+        None => {}
     }
 
-    let config: Config = serde_json::from_str(&fs::read_to_string(CONFIG_FILE)?)?;
+    let mut config = load_config()?;
     debug!("config: {:#?}", config);
+    let _lock = instance::lock(cli.takeover, config.http.as_ref()).await?;
 
-    let mut app = match load_state(STATE_FILE) {
+    crash::install();
+    let path = if crash::newer_than(STATE_FILE) {
+        warn!("recovering from {}", crash::CRASH_FILE);
+        crash::CRASH_FILE
+    } else {
+        STATE_FILE
+    };
+    let mut app = match load_state(path, &config) {
         Ok(app) => app,
         Err(e) => {
             warn!("load state: {:#?}", e);
-            App::new(&config.refresh_token).await?
+            Tracker::new(&config.refresh_token, &config.tracker).await?
         }
     };
 
+    let mut notifiers = notifier::registry(&config);
+    let mut rules = Rules::new(
+        config.rules.clone(),
+        config.default_action,
+        &config.alert_keywords,
+        &app.unread,
+    )
+    .context("alert_keywords")?;
+    if cli.once {
+        return run_once(app, &config, &mut notifiers, &mut rules).await;
+    }
+    fire(&mut notifiers, &app, Event::Startup, "started".to_owned()).await;
+
+    let (tx, mut rx) = wake::channel();
     drop(fs::File::create(NOTIFY_FILE)?);
-    let inotify = Inotify::init()?;
-    inotify.watches().add(NOTIFY_FILE, WatchMask::OPEN)?;
-    let mut buf = [0; 128];
-    let mut inotify = inotify.into_event_stream(&mut buf)?;
-
-    let rx = Arc::new(Notify::new());
-    let tx = rx.clone();
-    ctrlc::set_handler(move || {
-        warn!("shutting down");
-        tx.notify_waiters();
-    })?;
-
-    let delay = Duration::from_secs(config.delay.into());
+    wake::inotify(NOTIFY_FILE, tx.clone())?;
+    wake::triggers(TRIGGERS_DIR, tx.clone())?;
+    wake::signals(tx.clone())?;
+    wake::resume(tx.clone());
+    #[cfg(feature = "hotkey")]
+    let _hotkeys = match &config.hotkey {
+        Some(h) => Some(hotkey::register(h, tx.clone())?),
+        None => None,
+    };
+
+    let (status, status_rx) = watch::channel(Status::default());
+    #[cfg(feature = "tui")]
+    let _tui = log.map(|log| tui::spawn(status_rx.clone(), tx.clone(), log));
+    if let Some(http) = &config.http {
+        let shared = Shared {
+            status: status_rx,
+            wake: tx.clone(),
+            thumbs: config.tracker.thumbs.clone(),
+            blur_sanity_level: config.tracker.blur_sanity_level.filter(|_| http.blur),
+            event_log: config.event_log.clone(),
+        };
+        server::spawn(http, shared).await?;
+    }
+
     let mut token = Default::default();
-    let mut itoa = itoa::Buffer::new();
-    let mut itoa2 = itoa::Buffer::new();
-    loop {
-        if let Err(e) = app.refresh(&config).await {
-            error!("refresh failed: {:#?}", e);
-            token = Default::default();
-        } else {
-            let since = app.since();
-            let ago = app.since_ago();
-            if token != app.token() {
-                token = app.token();
+    let mut alerted: Option<Instant> = None;
+    let mut alerted_dist = 0;
+    let mut new_since_alert = 0;
+    let mut pending = false;
+    let mut above = false;
+    let mut reminded = false;
+    let mut failures = 0;
+    let mut snooze: Option<Instant> = None;
+    let mut snoozed = false;
+    let mut offline = false;
+    let mut outage: Option<Outage> = None;
+    let mut server_errors = 0;
+    let mut remain_since: Option<Instant> = None;
+    let mut gap_pages = 0;
+    let mut gap_reported = false;
+    let mut web_reported = false;
+    let mut feeds = feeds::Feeds::new();
+    #[cfg(feature = "clipboard")]
+    let mut clipboard = clipboard::Clipboard::new(&app);
+    #[cfg(feature = "qr")]
+    let mut qr = qr::Qr::default();
+    let influx = influx::Influx::new();
+    let otel = config.otlp.as_ref().map(|_| otel::Otel::new());
+    let mut queued = VecDeque::new();
+    let stop = app.stopper();
+    let watchdog = Watchdog::spawn(
+        Duration::from_secs(2 * u64::from(config.delay) + u64::from(config.refresh_deadline)),
+        config.callbacks(),
+        config.watchdog_abort,
+    );
+    // A panic in the loop leaves what it had so far in `state.crash.json`.
+    let r = AssertUnwindSafe(async {
+        loop {
+            let delay = Duration::from_secs(config.delay.into());
+            let cooldown = Duration::from_secs(config.notify_cooldown.into());
+            let deadline = Duration::from_secs(config.refresh_deadline.into());
+            let probe = Duration::from_secs(config.offline_probe.into());
+            let outage_delay = Duration::from_secs(config.outage_delay.into());
+            let metered_delay = config
+                .metered_delay
+                .map_or(3 * u64::from(config.delay), u64::from);
+            let metered_delay = Duration::from_secs(metered_delay);
+
+            let was_snoozed = snoozed;
+            snoozed = snooze.is_some_and(|t| Instant::now() < t);
+            if snoozed != was_snoozed {
+                info!("snooze {}", if snoozed { "started" } else { "ended" });
+            }
+            if was_snoozed && !snoozed {
+                // Summarize what arrived during the snooze in a single alert.
+                pending = true;
+                alerted = None;
+            }
+
+            if offline && online().await {
+                info!("back online");
+                offline = false;
+            }
+            let battery = config.delay_on_battery.is_some() && power::on_battery();
+            if battery != app.on_battery() {
                 info!(
-                    "{}{}{} illusts since {} ({}, {})",
-                    if app.remain { "> " } else { "" },
-                    if app.skip { "~ " } else { "" },
-                    app.dist(),
-                    since,
-                    ago,
-                    app.iid
+                    "running on {}",
+                    if battery { "battery" } else { "AC power" }
                 );
+                app.set_on_battery(battery);
+            }
+            watchdog.phase("refresh");
+            let started = Instant::now();
+            let span_start = trace::now();
+            let r = if offline {
+                Err(anyhow::Error::msg(Offline))
+            } else {
+                let mut refresh = pin!(timeout(deadline, app.refresh(&config.tracker)));
+                // Keep listening so a shutdown ends the scan at a page boundary instead of waiting
+                // for all of it.
+                let r = 'refresh: loop {
+                    let w = tokio::select! {
+                        r = &mut refresh => break 'refresh r,
+                        w = rx.recv() => w.unwrap_or(Wake::Shutdown),
+                    };
+                    match w {
+                        Wake::Shutdown => {
+                            if !stop.swap(true, Ordering::Relaxed) {
+                                info!("finishing the refresh before shutting down");
+                            }
+                            queued.push_back(Wake::Shutdown);
+                        }
+                        Wake::ManualRefresh => debug!("coalesced refresh request"),
+                        w => queued.push_back(w),
+                    }
+                };
+                match r {
+                    Ok(r) => r,
+                    Err(_) => Err(anyhow!("refresh exceeded {}s", config.refresh_deadline)),
+                }
+            };
+            let took = started.elapsed();
+            let attrs = vec![("count", app.dist().to_string())];
+            trace::record("refresh", span_start, r.is_ok(), attrs);
+            crash::update(&app, config.keyring);
+            if let Some(ic) = &config.influx {
+                if let Err(e) = influx.push(ic, &app, r.as_ref().ok(), took).await {
+                    error!("influx: {:#}", e);
+                }
+            }
+            watchdog.phase("notify");
+            if let Ok(delta) = &r {
+                debug!("refreshed: {:?}", delta);
+                if delta.web && !web_reported && !snoozed {
+                    web_reported = true;
+                    let msg =
+                        "the refresh token failed, counting through the website feed".to_owned();
+                    fire(&mut notifiers, &app, Event::ReauthNeeded, msg).await;
+                } else if !delta.web {
+                    web_reported = false;
+                }
+                #[cfg(feature = "wallpaper")]
+                if let Some(w) = config.wallpaper.as_ref().filter(|_| delta.anchor_changed) {
+                    if let Err(e) = w.apply(&app).await {
+                        error!("wallpaper: {:#}", e);
+                    }
+                }
+                #[cfg(feature = "clipboard")]
+                if let Some(c) = &config.clipboard {
+                    if let Err(e) = clipboard.update(c, &app) {
+                        error!("clipboard: {:#}", e);
+                    }
+                }
+                #[cfg(feature = "qr")]
+                if let Some(q) = &config.qr {
+                    if let Err(e) = qr.update(q, &app) {
+                        error!("qr: {:#}", e);
+                    }
+                }
+                if app.remain {
+                    let since = *remain_since.get_or_insert_with(Instant::now);
+                    gap_pages = gap_pages.max(delta.pages);
+                    let hours = since.elapsed().as_secs() / 3600;
+                    let due =
+                        config.gap_report_hours > 0 && hours >= config.gap_report_hours.into();
+                    if due && !gap_reported {
+                        gap_reported = true;
+                        // Pages hold about 30 illusts each.
+                        let msg = format!(
+                            "backlog exceeds {} pages (~{} illusts) for {} hours; \
+                             consider raising max_pages",
+                            gap_pages,
+                            gap_pages * 30,
+                            hours
+                        );
+                        warn!("{}", msg);
+                        fire(&mut notifiers, &app, Event::Gap, msg).await;
+                    }
+                } else {
+                    remain_since = None;
+                    gap_pages = 0;
+                    gap_reported = false;
+                }
+            }
+            if let Err(mut e) = r {
+                let detected = Outage::detect(&e);
+                if let Some(o) = detected {
+                    server_errors += 1;
+                    let storm = server_errors >= config.outage_threshold;
+                    if outage.is_none() && (o == Outage::Maintenance || storm) {
+                        warn!("pixiv {}, backing off: {:#}", o.as_str(), e);
+                        outage = Some(o);
+                        if !snoozed {
+                            let msg = format!("pixiv {}: {:#}", o.as_str(), e);
+                            fire(&mut notifiers, &app, Event::Outage, msg).await;
+                        }
+                    }
+                } else {
+                    server_errors = 0;
+                    // The anchor is unknown now, announce it again once refreshes succeed.
+                    token = Default::default();
+                }
+                if !offline
+                    && detected.is_none()
+                    && e.downcast_ref::<AuthFailed>().is_none()
+                    && !online().await
+                {
+                    warn!("network unreachable, pausing refreshes: {:#}", e);
+                    offline = true;
+                    e = anyhow::Error::msg(Offline);
+                }
+                if !offline && outage.is_none() {
+                    error!("refresh failed: {:#?}", e);
+                    failures += 1;
+                }
+                // Only report failures that persist, transient ones are common on mobile networks.
+                if !offline && outage.is_none() && failures == config.error_threshold && !snoozed {
+                    let event = if e.downcast_ref::<AuthFailed>().is_some() {
+                        Event::ReauthNeeded
+                    } else {
+                        Event::Error
+                    };
+                    let msg = format!("{} consecutive failures: {:#}", failures, e);
+                    fire(&mut notifiers, &app, event, msg).await;
+                }
+                if let Some(bar) = &config.statusbar {
+                    if let Err(e) = bar.error(&e) {
+                        error!("statusbar: {:#?}", e);
+                    }
+                }
+                status.send_modify(|s| s.error = Some(format!("{:#}", e)));
+            } else {
+                if config.error_threshold > 0 && failures >= config.error_threshold {
+                    info!("recovered after {} failures", failures);
+                }
+                failures = 0;
+                server_errors = 0;
+                if let Some(o) = outage.take() {
+                    info!("pixiv recovered from {}", o.as_str());
+                }
+                status.send_replace(Status::new(&app));
+
+                if let Some(bar) = &config.statusbar {
+                    if let Err(e) = bar.update(&app) {
+                        error!("statusbar: {:#?}", e);
+                    }
+                }
+
+                if feeds
+                    .poll(&mut app, &config, &mut notifiers, &mut queued)
+                    .await
+                {
+                    status.send_replace(Status::new(&app));
+                }
+                if let Some(d) = config.digest.as_ref().filter(|d| d.due(&app)) {
+                    match d.run(&mut app, &config.tracker).await {
+                        Ok(msg) => fire(&mut notifiers, &app, Event::Digest, msg).await,
+                        Err(e) => error!("digest: {:#}", e),
+                    }
+                }
+
+                let since = app.since();
+                let ago = app.since_ago();
+                let verdict = rules.classify(&app.unread);
+                let action = verdict.action;
+                fire_keywords(&mut notifiers, &app, verdict.keywords).await;
+                if token != app.token() {
+                    // The default token stands for an unknown count, e.g. after a failure.
+                    if let Some(log) = config
+                        .event_log
+                        .as_ref()
+                        .filter(|_| token != Default::default())
+                    {
+                        let entry = eventlog::Entry::new(&app, token, took);
+                        if let Err(e) = log.append(&entry) {
+                            error!("event log: {:#}", e);
+                        }
+                    }
+                    token = app.token();
+                    let summary = app
+                        .messages()
+                        .summary(&app.dist().to_string(), &since, &ago);
+                    info!(
+                        "{}{}{} [{}]",
+                        if app.remain { "> " } else { "" },
+                        if app.skip { "~ " } else { "" },
+                        summary,
+                        app.iid
+                    );
+                    if action != Some(Action::Quiet) {
+                        pending = true;
+                    }
+                }
+                let push = action == Some(Action::Push);
+                if push {
+                    info!("push rule matched");
+                    pending = true;
+                    alerted = None;
+                }
+
+                let reached = app.dist() >= config.notify_threshold;
+                let reminder = config.notify_reminder.is_some_and(|n| app.dist() >= n);
+                let remind = reminder && !reminded;
+                reminded = reminder;
+                if remind {
+                    info!("reminder: {} illusts", app.dist());
+                    pending = true;
+                    alerted = None;
+                }
+
+                // Coalesce token changes within the cooldown window into one alert.
+                let alert = !snoozed
+                    && pending
+                    && (reached || push)
+                    && alerted.is_none_or(|t| t.elapsed() >= cooldown);
+                if alert {
+                    new_since_alert = app.dist().saturating_sub(alerted_dist);
+                    alerted_dist = app.dist();
+                    alerted = Some(Instant::now());
+                    pending = false;
+                }
+
+                let payload = Payload {
+                    new_since_alert,
+                    reminder: remind,
+                    ..Payload::new(&app, Event::CountChanged)
+                };
+
+                if alert {
+                    notifier::dispatch(&mut notifiers, &app, &payload).await;
+                }
+
+                if snoozed {
+                    debug!("snoozed, {} illusts", app.dist());
+                } else if !reached && !above {
+                    debug!("below threshold, {} illusts", app.dist());
+                } else if pending && reached {
+                    debug!("cooling down, {} illusts pending", app.dist());
+                } else {
+                    let payload = Payload {
+                        event: Event::Refresh,
+                        ..payload
+                    };
+                    notifier::dispatch(&mut notifiers, &app, &payload).await;
+                }
+                above = reached;
             }
 
-            let args = &[
-                itoa.format(app.dist()),
-                itoa2.format(app.iid),
-                &app.since(),
-                &ago,
-                if app.remain { "1" } else { "0" },
-                if app.skip { "1" } else { "0" },
-            ];
-
-            if let Err(e) = notify(CALLBACK_FILE, args) {
-                error!("callback: {:#?}", e);
+            // Refresh requests that arrived during the refresh are already served.
+            while let Ok(w) = rx.try_recv() {
+                match w {
+                    Wake::ManualRefresh => debug!("coalesced refresh request"),
+                    w => queued.push_back(w),
+                }
             }
-        }
 
-        while let Some(e) = inotify.next().now_or_never() {
-            info!("inotify: {:#?}", e);
-        }
+            if let (Some(o), Some(oc)) = (&otel, &config.otlp) {
+                if let Err(e) = o.export(oc, app.request_timeout()).await {
+                    error!("otlp: {:#}", e);
+                }
+            }
 
-        tokio::select! {
-            _ = sleep(delay) => {},
-            _ = rx.notified() => {
-                info!("dumping state");
-                fs::write(STATE_FILE, serde_json::to_string_pretty(&app.dump())?)?;
-                return Ok(());
-            },
-            r = inotify.next() => {
-                match r {
-                    Some(Ok(_)) => {
-                        info!("refreshing");
+            let wait = if offline {
+                probe
+            } else if outage.is_some() {
+                outage_delay
+            } else if app.metered() || app.throttled(&config.tracker) {
+                metered_delay
+            } else if let Some(d) = config.delay_on_battery.filter(|_| app.on_battery()) {
+                Duration::from_secs(d.into())
+            } else if config.schedule.is_some() {
+                Duration::ZERO
+            } else {
+                delay
+            };
+            let wait = match app.budget_wait(&config.tracker) {
+                Some(w) if w > wait => {
+                    warn!("API budget used up, waiting {}s", w.as_secs());
+                    w
+                }
+                _ => wait,
+            };
+            let wait = jitter(wait, config.delay_jitter_pct);
+            let wait = match &config.schedule {
+                Some(s) => s.wait(app.tz, wait).unwrap_or_else(|| {
+                    warn!("schedule matches no more times, waiting delay");
+                    wait.max(delay)
+                }),
+                None => wait,
+            };
+            let next = Instant::now() + wait;
+            // Time for this wait and the next refresh, with room for slow notifications.
+            watchdog.beat("wait", 2 * wait + deadline);
+            'wait: loop {
+                let w = match queued.pop_front() {
+                    Some(w) => w,
+                    None => {
+                        // Feeds due shortly before the refresh are polled right after it, and all
+                        // of them wait for it on a schedule.
+                        let side = feeds.next(&config).filter(|t| {
+                            *t + feeds::COALESCE < next
+                                && !offline
+                                && outage.is_none()
+                                && config.schedule.is_none()
+                        });
+                        tokio::select! {
+                            _ = sleep_until(next) => Wake::Tick,
+                            _ = sleep_until(side.unwrap_or(next)), if side.is_some() => {
+                                watchdog.phase("feeds");
+                                if feeds
+                                    .poll(&mut app, &config, &mut notifiers, &mut queued)
+                                    .await
+                                {
+                                    status.send_replace(Status::new(&app));
+                                }
+                                watchdog.phase("wait");
+                                continue 'wait;
+                            }
+                            w = rx.recv() => w.unwrap_or(Wake::Shutdown),
+                        }
                     }
-                    r => {
-                        bail!("inotify: {:?}", r);
+                };
+                match w {
+                    Wake::Tick => break,
+                    Wake::ManualRefresh => {
+                        info!("refresh requested");
+                        alerted = None;
+                        token = Default::default();
+                        app.invalidate();
+                        break;
+                    }
+                    Wake::Status => {
+                        let msg = app.summary();
+                        fire(&mut notifiers, &app, Event::Status, msg).await;
+                    }
+                    Wake::Opened(id) if config.dismiss_opened => {
+                        if app.dismiss(id) {
+                            info!("dismissed {}", id);
+                            status.send_replace(Status::new(&app));
+                        }
+                    }
+                    Wake::Opened(_) => {}
+                    Wake::Resumed => {
+                        // The refresh checks the session first and waits out a network that is not
+                        // back yet as offline.
+                        app.invalidate();
+                        break;
+                    }
+                    Wake::Shutdown => {
+                        fire(
+                            &mut notifiers,
+                            &app,
+                            Event::Shutdown,
+                            "shutting down".to_owned(),
+                        )
+                        .await;
+                        return Ok::<_, anyhow::Error>(());
+                    }
+                    Wake::ConfigReload => match load_config() {
+                        Ok(c) => match Rules::new(
+                            c.rules.clone(),
+                            c.default_action,
+                            &c.alert_keywords,
+                            &app.unread,
+                        ) {
+                            Ok(r) => {
+                                // The running client keeps the token it signed in with.
+                                let authed = if c.refresh_token == config.refresh_token {
+                                    Ok(())
+                                } else {
+                                    app.reauth(&c.refresh_token).await
+                                };
+                                match authed {
+                                    Ok(()) => {
+                                        info!("config reloaded");
+                                        config = c;
+                                        app.configure(&config.tracker);
+                                        notifiers = notifier::registry(&config);
+                                        rules = r;
+                                    }
+                                    Err(e) => error!("reload config: refresh_token: {:#}", e),
+                                }
+                            }
+                            Err(e) => error!("reload config: alert_keywords: {:#}", e),
+                        },
+                        Err(e) => error!("reload config: {:#}", e),
+                    },
+                    Wake::Snooze(until) => {
+                        info!("snooze requested: {:?}", until.map(|t| t - Instant::now()));
+                        snooze = until;
+                    }
+                    Wake::Metered(on) => {
+                        let on = on.unwrap_or(!app.metered());
+                        if app.metered() != on {
+                            app.set_metered(on);
+                            info!("metered mode {}", if on { "on" } else { "off" });
+                        }
+                    }
+                    Wake::MarkRead if config.tracker.anchor == AnchorMode::Bookmark => {
+                        if app.searches.is_empty() {
+                            warn!("mark read ignored, the anchor is the newest bookmark");
+                        } else {
+                            info!("marked the searches read");
+                            app.mark_searches_read();
+                            status.send_replace(Status::new(&app));
+                        }
+                    }
+                    Wake::MarkRead => {
+                        app.mark_searches_read();
+                        if let Err(e) = app.mark_read() {
+                            error!("mark read: {:#}", e);
+                        }
+                        break;
                     }
                 }
-                token = Default::default();
             }
         }
+    })
+    .catch_unwind()
+    .await;
+    match r {
+        Ok(r) => r?,
+        Err(panic) => {
+            crash::dump(&app, config.keyring);
+            panic::resume_unwind(panic);
+        }
     }
+    info!("dumping state");
+    save_state(app, &config)
 }
@@ -0,0 +1,287 @@
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use illust_notify::{ImageSize, Tracker};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::apprise::{percent_decode, percent_encode};
+use crate::callback::{Event, Payload};
+use crate::notifier::Notifier;
+use crate::template;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MatrixConfig {
+    /// Base URL of the homeserver, e.g. `https://matrix.org`.
+    homeserver: String,
+    access_token: String,
+    /// Room id (`!abc:example.org`) or alias (`#abc:example.org`) the bot has joined.
+    room: String,
+    /// Template for the text message, see [`crate::template`].
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default = "illust_notify::process::default_blur")]
+    blur: bool,
+}
+
+impl MatrixConfig {
+    /// Parses the part of an Apprise `matrix://` URL after the scheme.
+    pub fn from_url(https: bool, rest: &str) -> Result<Self> {
+        let rest = rest.split('?').next().unwrap_or_default();
+        let (token, path) = rest.split_once('@').context("missing access token")?;
+        if token.contains(':') {
+            bail!("user/password login is not supported, use an access token");
+        }
+        let (host, room) = path.split_once('/').context("missing room")?;
+        let room = percent_decode(room.trim_end_matches('/'));
+        if room.is_empty() {
+            bail!("missing room");
+        }
+        Ok(Self {
+            homeserver: format!("{}://{}", if https { "https" } else { "http" }, host),
+            access_token: percent_decode(token),
+            room,
+            body: None,
+            blur: true,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RoomAlias {
+    room_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Upload {
+    content_uri: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ImageInfo {
+    mimetype: &'static str,
+    size: usize,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "msgtype")]
+enum Message<'a> {
+    #[serde(rename = "m.text")]
+    Text {
+        body: &'a str,
+        format: &'static str,
+        formatted_body: &'a str,
+    },
+    #[serde(rename = "m.image")]
+    Image {
+        body: &'a str,
+        url: &'a str,
+        info: ImageInfo,
+    },
+}
+
+fn escape_html(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => r.push_str("&amp;"),
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '"' => r.push_str("&quot;"),
+            c => r.push(c),
+        }
+    }
+    r
+}
+
+pub struct Matrix {
+    config: MatrixConfig,
+    client: reqwest::Client,
+    room_id: Option<String>,
+    txn: u64,
+}
+
+impl Matrix {
+    pub fn new(config: MatrixConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            room_id: None,
+            txn: 0,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.homeserver.trim_end_matches('/'), path)
+    }
+
+    async fn room_id(&mut self) -> Result<String> {
+        if let Some(id) = &self.room_id {
+            return Ok(id.clone());
+        }
+        let id = if self.config.room.starts_with('#') {
+            let path = format!(
+                "/_matrix/client/v3/directory/room/{}",
+                percent_encode(&self.config.room)
+            );
+            let r: RoomAlias = self
+                .client
+                .get(&self.url(&path))
+                .bearer_auth(&self.config.access_token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            debug!("matrix: {} is {}", self.config.room, r.room_id);
+            r.room_id
+        } else {
+            self.config.room.clone()
+        };
+        self.room_id = Some(id.clone());
+        Ok(id)
+    }
+
+    async fn upload(&self, data: Vec<u8>, mimetype: &str) -> Result<String> {
+        let filename = if mimetype == "image/png" {
+            "image.png"
+        } else {
+            "image.jpg"
+        };
+        let r: Upload = self
+            .client
+            .post(&self.url("/_matrix/media/v3/upload"))
+            .bearer_auth(&self.config.access_token)
+            .header("Content-Type", mimetype)
+            .query(&[("filename", filename)])
+            .body(data)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(r.content_uri)
+    }
+
+    async fn send(&mut self, msg: &Message<'_>) -> Result<()> {
+        // Transaction ids only need to be unique per access token.
+        self.txn += 1;
+        let txn = format!(
+            "illust-notify-{}-{}",
+            OffsetDateTime::now_utc().unix_timestamp(),
+            self.txn
+        );
+        let path = format!(
+            "/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            percent_encode(&self.room_id().await?),
+            txn
+        );
+        self.client
+            .put(&self.url(&path))
+            .bearer_auth(&self.config.access_token)
+            .json(msg)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn default_text(app: &Tracker) -> (String, String) {
+        let mut body = app.summary();
+        let mut html = app.messages().summary(
+            &format!("<b>{}</b>", app.dist_text()),
+            &app.since(),
+            &app.since_ago(),
+        );
+        let newest = app.newest_shown();
+        if let Some(illust) = newest {
+            let url = format!("https://www.pixiv.net/artworks/{}", illust.id);
+            let m = app.messages();
+            let by = m.by(&illust.title, &illust.user.name);
+            body += &format!("\n{} {}", m.newest(&by), url);
+            let title = format!("<a href=\"{}\">{}</a>", url, escape_html(&illust.title));
+            html += &format!(
+                "<br>{}",
+                m.newest(&m.by(&title, &escape_html(&illust.user.name)))
+            );
+        }
+        (body, html)
+    }
+
+    /// Posts the count and a link to the newest unread illust, followed by its thumbnail.
+    pub async fn notify(&mut self, app: &Tracker, payload: &Payload<'_>) -> Result<()> {
+        let newest = app.newest_shown();
+        let (body, html) = match &self.config.body {
+            Some(t) => {
+                let body = template::render(t, payload);
+                let html = escape_html(&body).replace('\n', "<br>");
+                (body, html)
+            }
+            None => Self::default_text(app),
+        };
+        // A hung homeserver would hold up the main loop.
+        let msg = Message::Text {
+            body: &body,
+            format: "org.matrix.custom.html",
+            formatted_body: &html,
+        };
+        app.timed("matrix", self.send(&msg)).await?;
+
+        if let Some(illust) = newest.filter(|_| app.downloads()) {
+            let blurred = self.config.blur && app.blurred(illust);
+            let size = app.image_size();
+            let cached = if blurred {
+                // Only the cached thumbnail can be blurred, leave the image out without it.
+                let Some(path) = payload.thumb_for(true) else {
+                    return Ok(());
+                };
+                Some(path.clone())
+            } else {
+                app.thumb(illust.id)
+                    .filter(|_| size == ImageSize::SquareMedium)
+            };
+            let url = illust.image_url(size);
+            let data = match cached {
+                Some(path) => fs::read(path)?,
+                None => app.download(url).await?,
+            };
+            // Originals keep the format they were uploaded in, the smaller sizes are always JPEG.
+            let mimetype = if url.ends_with(".png") && !blurred {
+                "image/png"
+            } else {
+                "image/jpeg"
+            };
+            let size = data.len();
+            let uri = app
+                .timed("matrix upload", self.upload(data, mimetype))
+                .await?;
+            let msg = Message::Image {
+                body: &illust.title,
+                url: &uri,
+                info: ImageInfo { mimetype, size },
+            };
+            app.timed("matrix", self.send(&msg)).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Notifier for Matrix {
+    fn name(&self) -> &str {
+        "matrix"
+    }
+
+    fn accepts(&self, event: Event) -> bool {
+        event == Event::CountChanged
+    }
+
+    fn notify<'a>(
+        &'a mut self,
+        app: &'a Tracker,
+        payload: &'a Payload<'_>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        Matrix::notify(self, app, payload).boxed_local()
+    }
+}
@@ -0,0 +1,87 @@
+//! Notification backends behind one trait, collected from the config at startup.
+//!
+//! Adding a backend means implementing [`Notifier`] in its own module and registering it in
+//! [`registry`].
+
+use anyhow::Result;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use illust_notify::{trace, Tracker};
+
+use crate::callback::{Event, Payload};
+use crate::{apprise, Config};
+
+pub trait Notifier {
+    fn name(&self) -> &str;
+
+    /// Whether the backend is subscribed to `event`.
+    fn accepts(&self, event: Event) -> bool;
+
+    fn notify<'a>(
+        &'a mut self,
+        app: &'a Tracker,
+        payload: &'a Payload<'_>,
+    ) -> LocalBoxFuture<'a, Result<()>>;
+}
+
+/// Every backend enabled in the config, in a stable order.
+pub fn registry(config: &Config) -> Vec<Box<dyn Notifier>> {
+    let mut r: Vec<Box<dyn Notifier>> = Vec::new();
+    for cb in config.callbacks() {
+        r.push(Box::new(cb));
+    }
+    if let Some(c) = &config.termux {
+        r.push(Box::new(crate::termux::Termux::new(c.clone())));
+    }
+    if let Some(c) = &config.launcher {
+        r.push(Box::new(crate::launcher::Launcher::new(c.clone())));
+    }
+    if let Some(c) = &config.matrix {
+        r.push(Box::new(crate::matrix::Matrix::new(c.clone())));
+    }
+    for url in &config.notify_urls {
+        match apprise::parse(url) {
+            Ok(apprise::Backend::Matrix(c)) => r.push(Box::new(crate::matrix::Matrix::new(c))),
+            Ok(apprise::Backend::Webhook(w)) => r.push(Box::new(w)),
+            Err(e) => warn!("skipping notify url {:?}: {:#}", url, e),
+        }
+    }
+    r
+}
+
+/// Sends the payload to every backend subscribed to its event, logging failures.
+pub async fn dispatch(notifiers: &mut [Box<dyn Notifier>], app: &Tracker, payload: &Payload<'_>) {
+    for n in notifiers {
+        if n.accepts(payload.event) {
+            let start = trace::now();
+            let r = n.notify(app, payload).await;
+            let attrs = vec![
+                ("backend", n.name().to_owned()),
+                ("event", payload.event.as_str().to_owned()),
+            ];
+            trace::record("notify", start, r.is_ok(), attrs);
+            if let Err(e) = r {
+                error!("{}: {:#?}", n.name(), e);
+            }
+        }
+    }
+}
+
+impl Notifier for crate::callback::CallbackConfig {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn accepts(&self, event: Event) -> bool {
+        self.events.contains(&event)
+    }
+
+    fn notify<'a>(
+        &'a mut self,
+        _: &'a Tracker,
+        payload: &'a Payload<'_>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        let r = self.run(payload);
+        async move { r }.boxed_local()
+    }
+}
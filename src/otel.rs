@@ -0,0 +1,204 @@
+//! Exports each refresh cycle as a trace over OTLP/HTTP with the JSON encoding.
+//!
+//! The spans of [`illust_notify::trace`] recorded during a cycle become children of one root
+//! span named `cycle`, so Jaeger or Tempo show the API calls and notifications of a slow refresh
+//! side by side. `endpoint` is the collector base URL, e.g. `http://localhost:4318`.
+
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+
+use anyhow::Result;
+use illust_notify::trace::{self, Span};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+fn default_service_name() -> String {
+    "illust-notify".to_owned()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OtlpConfig {
+    endpoint: String,
+    #[serde(default = "default_service_name")]
+    service_name: String,
+    /// Sent with every export, e.g. `Authorization` for a hosted collector.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    resource_spans: [ResourceSpans; 1],
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+    resource: Resource,
+    scope_spans: [ScopeSpans; 1],
+}
+
+#[derive(Serialize, Debug)]
+struct Resource {
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Serialize, Debug)]
+struct Scope {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct ScopeSpans {
+    scope: Scope,
+    spans: Vec<OtlpSpan>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    parent_span_id: String,
+    name: String,
+    /// Always `SPAN_KIND_INTERNAL`.
+    kind: u8,
+    /// Decimal strings, as in the protobuf JSON mapping of 64-bit integers.
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue>,
+    status: Status,
+}
+
+#[derive(Serialize, Debug)]
+struct Status {
+    /// 1 for ok, 2 for error.
+    code: u8,
+}
+
+#[derive(Serialize, Debug)]
+struct KeyValue {
+    key: String,
+    value: AnyValue,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AnyValue {
+    string_value: String,
+}
+
+impl KeyValue {
+    fn new(key: &str, value: String) -> Self {
+        Self {
+            key: key.to_owned(),
+            value: AnyValue {
+                string_value: value,
+            },
+        }
+    }
+}
+
+/// A random id of `bytes` bytes in hex; the std hasher is seeded randomly per instance.
+fn random_id(bytes: usize) -> String {
+    let mut id = String::with_capacity(bytes * 2);
+    while id.len() < bytes * 2 {
+        let mut h = RandomState::new().build_hasher();
+        h.write_i128(trace::now());
+        id.push_str(&format!("{:016x}", h.finish()));
+    }
+    id.truncate(bytes * 2);
+    id
+}
+
+fn otlp_span(trace_id: &str, parent: &str, span: Span) -> OtlpSpan {
+    OtlpSpan {
+        trace_id: trace_id.to_owned(),
+        span_id: random_id(8),
+        parent_span_id: parent.to_owned(),
+        name: span.name,
+        kind: 1,
+        start_time_unix_nano: span.start.to_string(),
+        end_time_unix_nano: span.end.to_string(),
+        attributes: span
+            .attrs
+            .into_iter()
+            .map(|(k, v)| KeyValue::new(k, v))
+            .collect(),
+        status: Status {
+            code: if span.ok { 1 } else { 2 },
+        },
+    }
+}
+
+pub struct Otel {
+    client: reqwest::Client,
+}
+
+impl Otel {
+    pub fn new() -> Self {
+        trace::enable();
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sends the spans recorded since the last export as one trace.
+    pub async fn export(&self, config: &OtlpConfig, timeout: Duration) -> Result<()> {
+        let spans = trace::take();
+        let (Some(start), Some(end)) = (
+            spans.iter().map(|s| s.start).min(),
+            spans.iter().map(|s| s.end).max(),
+        ) else {
+            return Ok(());
+        };
+        let trace_id = random_id(16);
+        let root = otlp_span(
+            &trace_id,
+            "",
+            Span {
+                name: "cycle".to_owned(),
+                start,
+                end,
+                ok: spans.iter().all(|s| s.ok),
+                attrs: Vec::new(),
+            },
+        );
+        let mut out = Vec::with_capacity(spans.len() + 1);
+        for span in spans {
+            out.push(otlp_span(&trace_id, &root.span_id, span));
+        }
+        out.push(root);
+
+        let body = Export {
+            resource_spans: [ResourceSpans {
+                resource: Resource {
+                    attributes: vec![KeyValue::new("service.name", config.service_name.clone())],
+                },
+                scope_spans: [ScopeSpans {
+                    scope: Scope {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                    },
+                    spans: out,
+                }],
+            }],
+        };
+        let url = format!("{}/v1/traces", config.endpoint.trim_end_matches('/'));
+        let mut req = self
+            .client
+            .post(&url)
+            .timeout(timeout)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&body)?);
+        for (k, v) in &config.headers {
+            req = req.header(k, v);
+        }
+        req.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
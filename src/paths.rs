@@ -0,0 +1,81 @@
+//! Where `config.json`, the callback script and the state files live.
+//!
+//! An explicit directory, or a `config.json` in the working directory, keeps everything in that
+//! one directory as before. Otherwise the config goes to the platform config directory, e.g.
+//! `$XDG_CONFIG_HOME/illust-notify`, and the daemon runs in the platform state directory, e.g.
+//! `$XDG_STATE_HOME/illust-notify`, where `state.json`, `img.jpg` and `notify` are kept.
+//!
+//! `--portable` is an explicit directory that must also hold every other file the config names,
+//! so the directory can be moved or synced between machines as a whole.
+
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use directories::ProjectDirs;
+
+const CONFIG_FILE: &str = "config.json";
+const CALLBACK_FILE: &str = "callback";
+
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+static PORTABLE: AtomicBool = AtomicBool::new(false);
+
+/// Picks the layout and changes into the state directory.
+pub fn init(dir: Option<&Path>, portable: Option<&Path>) -> Result<()> {
+    let config_dir = if let Some(dir) = portable {
+        fs::create_dir_all(dir)?;
+        env::set_current_dir(dir)?;
+        PORTABLE.store(true, Ordering::Relaxed);
+        PathBuf::from(".")
+    } else if let Some(dir) = dir {
+        env::set_current_dir(dir)?;
+        PathBuf::from(".")
+    } else if Path::new(CONFIG_FILE).exists() {
+        PathBuf::from(".")
+    } else if let Some(dirs) = ProjectDirs::from("", "", "illust-notify") {
+        let state = dirs.state_dir().unwrap_or(dirs.data_local_dir());
+        fs::create_dir_all(state)?;
+        env::set_current_dir(state)?;
+        debug!("state directory: {}", state.display());
+        dirs.config_dir().to_owned()
+    } else {
+        PathBuf::from(".")
+    };
+    CONFIG_DIR.get_or_init(|| config_dir);
+    Ok(())
+}
+
+fn config_dir() -> &'static Path {
+    CONFIG_DIR.get().map_or(Path::new("."), |d| d.as_path())
+}
+
+pub fn config_file() -> PathBuf {
+    config_dir().join(CONFIG_FILE)
+}
+
+/// The script run when `callbacks` is not configured, next to `config.json`.
+pub fn callback_file() -> PathBuf {
+    config_dir().join(CALLBACK_FILE)
+}
+
+pub fn portable() -> bool {
+    PORTABLE.load(Ordering::Relaxed)
+}
+
+/// Fails if `path` of the config entry `what` would leave the portable directory.
+pub fn check_portable(what: &str, path: &Path) -> Result<()> {
+    let escapes = path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        bail!(
+            "{} {:?} must be relative and inside the directory with --portable",
+            what,
+            path
+        );
+    }
+    Ok(())
+}
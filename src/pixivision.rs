@@ -0,0 +1,108 @@
+//! Watches Pixivision for new articles, with the newest article seen kept in `state.json`.
+//!
+//! The first poll only records the newest article. Articles are sent as `pixivision` events;
+//! in `template` the article is `{title}`, its category `{caption}` and its URL `{message}`.
+
+use anyhow::Result;
+use illust_notify::{Tracker, TrackerConfig};
+use serde::Deserialize;
+use tokio::time::{Duration, Instant};
+
+use crate::callback::{Event, Payload};
+use crate::template;
+
+fn default_category() -> String {
+    "all".to_owned()
+}
+
+fn default_interval() -> u64 {
+    3600
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PixivisionConfig {
+    /// `all`, `illust`, `manga` or `cosplay`.
+    #[serde(default = "default_category")]
+    category: String,
+    /// Seconds between polls.
+    #[serde(default = "default_interval")]
+    interval: u64,
+    /// Template for the message, `{title} {message}` if missing.
+    template: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Article {
+    pub id: u64,
+    pub title: String,
+    pub article_url: String,
+    #[serde(default)]
+    pub subcategory_label: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Spotlight {
+    spotlight_articles: Vec<Article>,
+}
+
+#[derive(Default)]
+pub struct Pixivision {
+    next: Option<Instant>,
+}
+
+impl Pixivision {
+    /// When the next poll is due.
+    pub fn next(&self) -> Instant {
+        self.next.unwrap_or_else(Instant::now)
+    }
+
+    /// The articles published since the last poll, oldest first.
+    pub async fn poll(
+        &mut self,
+        app: &mut Tracker,
+        config: &TrackerConfig,
+        pv: &PixivisionConfig,
+    ) -> Result<Vec<Article>> {
+        self.next = Some(Instant::now() + Duration::from_secs(pv.interval));
+        let url = format!(
+            "https://app-api.pixiv.net/v1/spotlight/articles?category={}&filter=for_android",
+            pv.category
+        );
+        let r: Spotlight = app.call(config, &url).await?;
+        let newest = r.spotlight_articles.iter().map(|a| a.id).max();
+        let last = app.pixivision;
+        if let Some(newest) = newest {
+            app.pixivision = app.pixivision.max(newest);
+        }
+        if last == 0 {
+            debug!("pixivision: starting after {:?}", newest);
+            return Ok(Vec::new());
+        }
+        let mut articles: Vec<Article> = r
+            .spotlight_articles
+            .into_iter()
+            .filter(|a| a.id > last)
+            .collect();
+        articles.reverse();
+        Ok(articles)
+    }
+}
+
+impl PixivisionConfig {
+    pub fn payload<'a>(&self, app: &'a Tracker, article: &Article) -> Payload<'a> {
+        let payload = Payload {
+            message: Some(article.article_url.clone()),
+            title: article.title.clone(),
+            caption: article.subcategory_label.clone(),
+            ..Payload::new(app, Event::Pixivision)
+        };
+        let message = match &self.template {
+            Some(t) => template::render(t, &payload),
+            None => format!("{} {}", article.title, article.article_url),
+        };
+        Payload {
+            message: Some(message),
+            ..payload
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Tells whether the machine runs on battery, for `delay_on_battery`.
+//!
+//! Linux and Android read `/sys/class/power_supply`, macOS asks `pmset` and Windows
+//! `GetSystemPowerStatus`. Anything undetectable, like a desktop without a battery, counts as
+//! AC power.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn on_battery() -> bool {
+    use std::fs;
+
+    let Ok(dir) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let read = |path: std::path::PathBuf| {
+        fs::read_to_string(path)
+            .map(|s| s.trim().to_owned())
+            .unwrap_or_default()
+    };
+    let mut discharging = false;
+    for entry in dir.flatten() {
+        let path = entry.path();
+        match read(path.join("type")).as_str() {
+            "Mains" | "USB" if read(path.join("online")) == "1" => return false,
+            "Battery" if read(path.join("status")) == "Discharging" => discharging = true,
+            _ => {}
+        }
+    }
+    discharging
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    use std::process::Command;
+
+    match Command::new("pmset").args(["-g", "batt"]).output() {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).contains("'Battery Power'"),
+        Err(e) => {
+            debug!("pmset: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn on_battery() -> bool {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // SAFETY: the struct is plain data and only written by the call.
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return false;
+    }
+    status.ACLineStatus == 0
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    windows
+)))]
+pub fn on_battery() -> bool {
+    false
+}
@@ -0,0 +1,106 @@
+//! Re-encodes the downloaded anchor image, so whatever reads `img.jpg` gets one size and format.
+//!
+//! Decoding and encoding again also drops EXIF and other metadata. The file keeps its name
+//! whatever the format; needs the `image` feature, as does [`blurred`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Jpeg,
+    Png,
+    /// Lossless, as that is all the `image` crate encodes.
+    Webp,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ProcessConfig {
+    /// Scale down to fit in a square of this many pixels, keeping the aspect ratio.
+    pub resize: Option<u32>,
+    /// Crop to fill the square instead of fitting in it.
+    #[serde(default)]
+    pub crop: bool,
+    #[serde(default)]
+    pub format: Format,
+}
+
+#[cfg(feature = "image")]
+pub fn apply(config: &ProcessConfig, path: &Path) -> Result<()> {
+    use image::imageops::FilterType;
+    use image::{DynamicImage, ImageFormat, ImageReader};
+
+    // Originals may be PNG whatever the file is named.
+    let mut img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    if let Some(n) = config.resize {
+        img = if config.crop {
+            img.resize_to_fill(n, n, FilterType::Lanczos3)
+        } else {
+            img.resize(n, n, FilterType::Lanczos3)
+        };
+    }
+    let format = match config.format {
+        Format::Jpeg => {
+            // JPEG has no alpha channel.
+            img = DynamicImage::from(img.to_rgb8());
+            ImageFormat::Jpeg
+        }
+        Format::Png => ImageFormat::Png,
+        Format::Webp => ImageFormat::WebP,
+    };
+    img.save_with_format(path, format)?;
+    debug!("processed {}", path.display());
+    Ok(())
+}
+
+#[cfg(not(feature = "image"))]
+pub fn apply(_: &ProcessConfig, _: &Path) -> Result<()> {
+    anyhow::bail!("built without the image feature")
+}
+
+#[cfg(feature = "image")]
+fn blur(src: &Path, dst: &Path) -> Result<()> {
+    use image::{DynamicImage, ImageFormat, ImageReader};
+
+    let img = ImageReader::open(src)?.with_guessed_format()?.decode()?;
+    // Strong enough that only the colors remain at thumbnail sizes.
+    let sigma = img.width().max(img.height()) as f32 / 20.0;
+    DynamicImage::from(img.blur(sigma).to_rgb8()).save_with_format(dst, ImageFormat::Jpeg)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "image"))]
+fn blur(_: &Path, _: &Path) -> Result<()> {
+    anyhow::bail!("built without the image feature")
+}
+
+/// Whether [`blurred`] can work, as it needs the `image` feature.
+pub const CAN_BLUR: bool = cfg!(feature = "image");
+
+/// The `blur` of the backends that show images, which `"blur": false` turns off to show
+/// them as is despite `blur_sanity_level`.
+pub fn default_blur() -> bool {
+    true
+}
+
+/// Where [`blurred`] keeps the copy of `path`, `<stem>.blur.jpg`.
+pub fn blur_path(path: &Path) -> PathBuf {
+    path.with_extension("blur.jpg")
+}
+
+/// A blurred copy of `path`, made again whenever `path` was modified after it.
+pub fn blurred(path: &Path) -> Result<PathBuf> {
+    let dst = blur_path(path);
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified());
+    let fresh = matches!((modified(path), modified(&dst)), (Ok(s), Ok(d)) if d >= s);
+    if !fresh {
+        blur(path, &dst)?;
+        debug!("blurred {}", path.display());
+    }
+    Ok(dst)
+}
@@ -0,0 +1,89 @@
+//! A QR code of the newest unread illust's URL, to carry on reading on a phone.
+//!
+//! `log` prints it with block characters at the info level, `png` keeps it in `qr.png` next to
+//! `img.jpg`, which needs the `image` feature. Both follow the newest unread illust, and the
+//! PNG is removed once nothing is left unread.
+
+use std::fs;
+
+use anyhow::Result;
+use illust_notify::Tracker;
+use pixiv::model::IllustId;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use serde::Deserialize;
+
+pub const QR_FILE: &str = "qr.png";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QrConfig {
+    #[serde(default)]
+    log: bool,
+    #[serde(default)]
+    png: bool,
+}
+
+#[cfg(feature = "image")]
+fn save(code: &QrCode) -> Result<()> {
+    use image::{GrayImage, ImageFormat, Luma};
+    use qrcode::Color;
+
+    // Pixels per module, and the blank border in modules that readers need.
+    const SCALE: usize = 8;
+    const QUIET: usize = 4;
+    let colors = code.to_colors();
+    let width = code.width();
+    let size = ((width + 2 * QUIET) * SCALE) as u32;
+    let img = GrayImage::from_fn(size, size, |x, y| {
+        let (x, y) = (x as usize / SCALE, y as usize / SCALE);
+        let inside = (QUIET..width + QUIET).contains(&x) && (QUIET..width + QUIET).contains(&y);
+        let dark = inside && colors[(y - QUIET) * width + x - QUIET] == Color::Dark;
+        Luma([if dark { 0 } else { 255 }])
+    });
+    img.save_with_format(QR_FILE, ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "image"))]
+fn save(_: &QrCode) -> Result<()> {
+    anyhow::bail!("built without the image feature")
+}
+
+/// Remembers the illust last encoded, so each is rendered once.
+#[derive(Default)]
+pub struct Qr {
+    last: Option<IllustId>,
+}
+
+impl Qr {
+    pub fn update(&mut self, config: &QrConfig, app: &Tracker) -> Result<()> {
+        let newest = app.newest_shown();
+        let id = newest.map(|i| i.id);
+        if id == self.last {
+            return Ok(());
+        }
+        self.last = id;
+        let Some(illust) = newest else {
+            if config.png {
+                let _ = fs::remove_file(QR_FILE);
+            }
+            return Ok(());
+        };
+        let url = format!("https://www.pixiv.net/artworks/{}", illust.id);
+        let code = QrCode::new(&url)?;
+        if config.log {
+            // Inverted, as terminals are mostly dark.
+            let text = code
+                .render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .build();
+            info!("{} {}\n{}", illust.title, url, text);
+        }
+        if config.png {
+            save(&code)?;
+            debug!("wrote {} for {}", QR_FILE, illust.id);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Watches pixiv rankings for followed artists and watched tags entering the top.
+//!
+//! Rankings are polled every `ranking_interval` seconds. The first poll after startup only
+//! records what is already ranked, so a restart does not announce it all again.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use illust_notify::{Illust, Tracker, TrackerConfig};
+use pixiv::model::IllustId;
+use serde::Deserialize;
+use tokio::time::{Duration, Instant};
+
+fn default_top() -> usize {
+    50
+}
+
+fn default_followed() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RankingConfig {
+    /// As the AppAPI names it, e.g. `day`, `week`, `month`, `day_male` or `week_rookie`.
+    mode: String,
+    /// Only the first this many places count.
+    #[serde(default = "default_top")]
+    top: usize,
+    /// Report followed artists.
+    #[serde(default = "default_followed")]
+    followed: bool,
+    /// Report illusts carrying any of these tags.
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl RankingConfig {
+    fn matches(&self, illust: &Illust) -> bool {
+        (self.followed && illust.user.is_followed)
+            || illust.tags.iter().any(|t| self.tags.contains(&t.name))
+    }
+}
+
+#[derive(Default)]
+pub struct Rankings {
+    next: Option<Instant>,
+    /// Matching illusts of the last poll in each mode, dropped once they leave the ranking.
+    seen: HashSet<(String, IllustId)>,
+    seeded: bool,
+}
+
+impl Rankings {
+    /// When the next poll is due, `None` without any rankings.
+    pub fn next(&self, configs: &[RankingConfig]) -> Option<Instant> {
+        (!configs.is_empty()).then(|| self.next.unwrap_or_else(Instant::now))
+    }
+
+    /// Fetches every ranking and describes the illusts that newly entered them.
+    pub async fn poll(
+        &mut self,
+        app: &mut Tracker,
+        config: &TrackerConfig,
+        configs: &[RankingConfig],
+        interval: Duration,
+    ) -> Result<Vec<String>> {
+        self.next = Some(Instant::now() + interval);
+        let mut msgs = Vec::new();
+        let mut seen = HashSet::new();
+        for ranking in configs {
+            let url = format!(
+                "https://app-api.pixiv.net/v1/illust/ranking?mode={}&filter=for_android",
+                ranking.mode
+            );
+            let illusts = app.fetch(config, &url, ranking.top).await?;
+            for (rank, illust) in illusts.iter().enumerate() {
+                if !ranking.matches(illust) || !app.shown(illust) {
+                    continue;
+                }
+                let key = (ranking.mode.clone(), illust.id);
+                if !self.seen.contains(&key) && self.seeded {
+                    msgs.push(format!(
+                        "{} by {} is #{} in {} (https://www.pixiv.net/artworks/{})",
+                        illust.title,
+                        illust.user.name,
+                        rank + 1,
+                        ranking.mode,
+                        illust.id
+                    ));
+                }
+                seen.insert(key);
+            }
+        }
+        self.seen = seen;
+        if !self.seeded {
+            debug!("rankings: {} already ranked", self.seen.len());
+            self.seeded = true;
+        }
+        Ok(msgs)
+    }
+}
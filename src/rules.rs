@@ -0,0 +1,170 @@
+//! Per-followee rules and keywords deciding how new illusts are announced.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use illust_notify::Illust;
+use pixiv::model::IllustId;
+use regex::Regex;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Counted, but never alerts on its own.
+    Quiet,
+    /// Alerts subject to the threshold and cooldown.
+    #[default]
+    Normal,
+    /// Alerts right away, ignoring the threshold and cooldown.
+    Push,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Rule {
+    /// Pixiv user ids of the artists.
+    users: Vec<u64>,
+    action: Action,
+}
+
+/// What the illusts new since the last refresh call for.
+pub struct Verdict<'a> {
+    /// The strongest action among them, `None` if there are none.
+    pub action: Option<Action>,
+    /// Illusts matching `alert_keywords`, with the matching pattern.
+    pub keywords: Vec<(&'a Illust, &'a str)>,
+}
+
+/// Classifies unread illusts as they appear, remembering which were already seen.
+pub struct Rules {
+    rules: Vec<Rule>,
+    default: Action,
+    keywords: Vec<Regex>,
+    seen: HashSet<IllustId>,
+}
+
+impl Rules {
+    pub fn new(
+        rules: Vec<Rule>,
+        default: Action,
+        keywords: &[String],
+        unread: &[Illust],
+    ) -> Result<Self> {
+        Ok(Self {
+            rules,
+            default,
+            keywords: keywords
+                .iter()
+                .map(|k| Regex::new(k))
+                .collect::<Result<_, _>>()?,
+            seen: unread.iter().map(|i| i.id).collect(),
+        })
+    }
+
+    fn action(&self, user: u64) -> Action {
+        self.rules
+            .iter()
+            .find(|r| r.users.contains(&user))
+            .map_or(self.default, |r| r.action)
+    }
+
+    /// The first keyword found in the title, caption or tags.
+    fn keyword(&self, illust: &Illust) -> Option<&str> {
+        let tags = illust
+            .tags
+            .iter()
+            .flat_map(|t| [Some(&t.name), t.translated_name.as_ref()])
+            .flatten();
+        let mut texts = [&illust.title, &illust.caption].into_iter().chain(tags);
+        texts
+            .find_map(|t| self.keywords.iter().find(|k| k.is_match(t)))
+            .map(|k| k.as_str())
+    }
+
+    /// Classifies the illusts new since the last call.
+    pub fn classify<'a>(&'a mut self, unread: &'a [Illust]) -> Verdict<'a> {
+        let new: Vec<_> = unread
+            .iter()
+            .filter(|i| !self.seen.contains(&i.id))
+            .collect();
+        self.seen = unread.iter().map(|i| i.id).collect();
+        Verdict {
+            action: new.iter().map(|i| self.action(i.user.id)).max(),
+            keywords: new
+                .into_iter()
+                .filter_map(|i| Some((i, self.keyword(i)?)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn illust(id: u64, user: u64, title: &str, tag: &str) -> Illust {
+        serde_json::from_value(json!({
+            "id": id,
+            "title": title,
+            "create_date": "2024-01-01T00:00:00+09:00",
+            "is_bookmarked": false,
+            "image_urls": { "square_medium": "" },
+            "user": { "id": user, "name": "" },
+            "tags": [{ "name": tag, "translated_name": null }],
+        }))
+        .unwrap()
+    }
+
+    fn rules(unread: &[Illust]) -> Rules {
+        let rules = vec![
+            Rule {
+                users: vec![1],
+                action: Action::Quiet,
+            },
+            Rule {
+                users: vec![2],
+                action: Action::Push,
+            },
+        ];
+        Rules::new(rules, Action::Normal, &["(?i)comic".to_owned()], unread).unwrap()
+    }
+
+    #[test]
+    fn classifies_only_new() {
+        let old = [illust(10, 2, "old", "")];
+        let mut r = rules(&old);
+        assert_eq!(r.classify(&old).action, None);
+
+        let unread = [illust(11, 1, "a", ""), old[0].clone()];
+        assert_eq!(r.classify(&unread).action, Some(Action::Quiet));
+        assert_eq!(r.classify(&unread).action, None);
+
+        let unread = [
+            illust(13, 3, "c", ""),
+            illust(12, 1, "b", ""),
+            old[0].clone(),
+        ];
+        assert_eq!(r.classify(&unread).action, Some(Action::Normal));
+        let unread = [illust(14, 2, "d", ""), illust(15, 1, "e", "")];
+        assert_eq!(r.classify(&unread).action, Some(Action::Push));
+    }
+
+    #[test]
+    fn keywords() {
+        let mut r = rules(&[]);
+        let unread = [
+            illust(20, 3, "Comic day", ""),
+            illust(21, 3, "plain", "4koma comic"),
+            illust(22, 3, "plain", "landscape"),
+        ];
+        let found: Vec<_> = r
+            .classify(&unread)
+            .keywords
+            .iter()
+            .map(|(i, k)| (i.id, *k))
+            .collect();
+        assert_eq!(found, [(20, "(?i)comic"), (21, "(?i)comic")]);
+    }
+}
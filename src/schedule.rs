@@ -0,0 +1,84 @@
+//! `schedule`: refreshes at the times matched by a cron expression instead of every `delay`.
+//!
+//! Standard five-field expressions like `*/10 9-23 * * 1-5` are read in `timezone`; the cron
+//! crate also takes its own six or seven fields, with seconds first and an optional year, in
+//! which days of the week count from 1 for Sunday.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::{FixedOffset, Utc};
+use serde::{de, Deserialize, Deserializer};
+use time::UtcOffset;
+use tokio::time::Duration;
+
+const WEEKDAYS: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+
+/// Spells out the numeric days of the week, which the cron crate counts differently.
+fn weekdays(field: &str) -> String {
+    let mut out = String::new();
+    let mut digits = String::new();
+    for c in field.chars().chain([',']) {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        // Steps stay numbers.
+        if !digits.is_empty() && !out.ends_with('/') {
+            match digits.parse::<usize>().ok().and_then(|d| WEEKDAYS.get(d)) {
+                Some(day) => out.push_str(day),
+                None => out.push_str(&digits),
+            }
+        } else {
+            out.push_str(&digits);
+        }
+        digits.clear();
+        out.push(c);
+    }
+    out.pop();
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct Schedule(cron::Schedule);
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let expr = match fields[..] {
+            [min, hour, dom, mon, dow] => {
+                format!("0 {} {} {} {} {}", min, hour, dom, mon, weekdays(dow))
+            }
+            _ => s.to_owned(),
+        };
+        Ok(Self(
+            expr.parse::<cron::Schedule>()
+                .with_context(|| format!("schedule {:?}", s))?,
+        ))
+    }
+}
+
+/// `deserialize_with` for an optional [`Schedule`].
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Schedule>, D::Error> {
+    match Option::<String>::deserialize(d)? {
+        Some(s) => s
+            .parse()
+            .map(Some)
+            .map_err(|e: anyhow::Error| de::Error::custom(format!("{:#}", e))),
+        None => Ok(None),
+    }
+}
+
+impl Schedule {
+    /// The wait until the first matching time after `at_least` from now, `None` if nothing
+    /// matches anymore.
+    pub fn wait(&self, tz: UtcOffset, at_least: Duration) -> Option<Duration> {
+        let tz = FixedOffset::east_opt(tz.whole_seconds())?;
+        let now = Utc::now().with_timezone(&tz);
+        let from = now + chrono::Duration::from_std(at_least).ok()?;
+        let next = self.0.after(&from).next()?;
+        (next - now).to_std().ok()
+    }
+}
@@ -0,0 +1,104 @@
+//! Tag searches followed as extra feeds, for tracking a tag without following anyone.
+//!
+//! Each search counts the results newer than its own mark, kept in `state.json`. A new search
+//! starts at zero, and `mark-read` moves the marks of all searches to their newest result.
+
+use anyhow::Result;
+use illust_notify::{SearchState, Tracker, TrackerConfig};
+use serde::Deserialize;
+use tokio::time::{Duration, Instant};
+
+use crate::apprise::percent_encode;
+
+fn default_sort() -> String {
+    "date_desc".to_owned()
+}
+
+fn default_target() -> String {
+    "partial_match_for_tags".to_owned()
+}
+
+fn default_limit() -> usize {
+    90
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SearchConfig {
+    word: String,
+    /// `date_desc`, `date_asc` or `popular_desc` (premium only).
+    #[serde(default = "default_sort")]
+    sort: String,
+    /// `partial_match_for_tags`, `exact_match_for_tags` or `title_and_caption`.
+    #[serde(default = "default_target")]
+    target: String,
+    /// `within_last_day`, `within_last_week` or `within_last_month`, unlimited if missing.
+    duration: Option<String>,
+    /// Most results looked at per poll.
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+impl SearchConfig {
+    fn url(&self) -> String {
+        let mut url = format!(
+            "https://app-api.pixiv.net/v1/search/illust?word={}&search_target={}&sort={}&filter=for_android",
+            percent_encode(&self.word),
+            self.target,
+            self.sort
+        );
+        if let Some(d) = &self.duration {
+            url += &format!("&duration={}", d);
+        }
+        url
+    }
+}
+
+#[derive(Default)]
+pub struct Searches {
+    next: Option<Instant>,
+}
+
+impl Searches {
+    /// When the next poll is due, `None` without any searches.
+    pub fn next(&self, configs: &[SearchConfig]) -> Option<Instant> {
+        (!configs.is_empty()).then(|| self.next.unwrap_or_else(Instant::now))
+    }
+
+    /// Polls every search and describes the ones whose count grew.
+    pub async fn poll(
+        &mut self,
+        app: &mut Tracker,
+        config: &TrackerConfig,
+        configs: &[SearchConfig],
+        interval: Duration,
+    ) -> Result<Vec<String>> {
+        self.next = Some(Instant::now() + interval);
+        // Searches removed from the config stop being counted.
+        app.searches
+            .retain(|word, _| configs.iter().any(|c| c.word == *word));
+        let mut msgs = Vec::new();
+        for search in configs {
+            let illusts = app.fetch(config, &search.url(), search.limit).await?;
+            let newest = illusts.iter().map(|i| i.id).max().unwrap_or_default();
+            let state = app
+                .searches
+                .entry(search.word.clone())
+                .or_insert_with(|| SearchState {
+                    mark: newest,
+                    ..Default::default()
+                });
+            let count = illusts.iter().filter(|i| i.id > state.mark).count();
+            state.newest = state.newest.max(newest);
+            if count > state.count {
+                msgs.push(format!(
+                    "{} new in {} (https://www.pixiv.net/tags/{}/artworks)",
+                    count,
+                    search.word,
+                    percent_encode(&search.word)
+                ));
+            }
+            state.count = count;
+        }
+        Ok(msgs)
+    }
+}
@@ -0,0 +1,146 @@
+//! Keeps the refresh token and the authed session in the OS keyring instead of plaintext files.
+//!
+//! A `refresh_token` of `"keyring"` in `config.json` is looked up at load time, and with
+//! `"keyring": true` the `api` session in `state.json` is replaced by the same marker on save.
+//! `illust-notify secrets migrate` moves existing plaintext secrets over.
+//!
+//! With `keyring_account` set, every entry is stored under `<entry>@<account>`, so several
+//! pixiv accounts can share one keyring and `refresh_token` can be left out of the config.
+//!
+//! `refresh_token` may also be `{"file": "..."}` or `{"command": "pass show pixiv"}`, read when
+//! the config is loaded or reloaded, so it never has to be written into `config.json`.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::RwLock;
+
+use anyhow::{bail, Context, Result};
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Stands in for a secret that lives in the keyring.
+pub const MARKER: &str = "keyring";
+
+pub const REFRESH_TOKEN: &str = "refresh_token";
+pub const SESSION: &str = "session";
+pub const WEB_SESSION: &str = "web_session";
+pub const FANBOX: &str = "fanbox";
+pub const INFLUX: &str = "influx";
+pub const SYNC: &str = "sync";
+
+static ACCOUNT: RwLock<Option<String>> = RwLock::new(None);
+
+/// Namespaces the entries by `keyring_account`, or stops doing so with `None`.
+pub fn set_account(account: Option<&str>) {
+    *ACCOUNT.write().unwrap_or_else(|e| e.into_inner()) = account.map(str::to_owned);
+}
+
+#[cfg(feature = "keyring")]
+fn entry(entry: &str) -> Result<keyring::Entry> {
+    let user = match &*ACCOUNT.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(account) => format!("{}@{}", entry, account),
+        None => entry.to_owned(),
+    };
+    Ok(keyring::Entry::new("illust-notify", &user)?)
+}
+
+#[cfg(feature = "keyring")]
+pub fn get(account: &str) -> Result<String> {
+    entry(account)?
+        .get_password()
+        .with_context(|| format!("keyring: {}", account))
+}
+
+#[cfg(feature = "keyring")]
+pub fn set(account: &str, secret: &str) -> Result<()> {
+    entry(account)?
+        .set_password(secret)
+        .with_context(|| format!("keyring: {}", account))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn get(_: &str) -> Result<String> {
+    bail!("built without the keyring feature")
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn set(_: &str, _: &str) -> Result<()> {
+    bail!("built without the keyring feature")
+}
+
+/// The secret itself, or the one stored under `account` if it is the marker.
+pub fn resolve(value: &str, account: &str) -> Result<String> {
+    if value == MARKER {
+        get(account)
+    } else {
+        Ok(value.to_owned())
+    }
+}
+
+/// Where a secret in `config.json` comes from.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum Source {
+    Inline(String),
+    /// The whole file, trimmed.
+    File {
+        file: PathBuf,
+    },
+    /// The first line printed by a `sh -c` command.
+    Command {
+        command: String,
+    },
+}
+
+impl Source {
+    fn read(self) -> Result<String> {
+        match self {
+            Source::Inline(s) => Ok(s),
+            Source::File { file } => Ok(fs::read_to_string(&file)
+                .with_context(|| format!("{}", file.display()))?
+                .trim()
+                .to_owned()),
+            Source::Command { command } => {
+                let out = Command::new("sh").arg("-c").arg(&command).output()?;
+                if !out.status.success() {
+                    bail!("{:?} exited with {}", command, out.status);
+                }
+                let out = String::from_utf8(out.stdout).context(command.clone())?;
+                match out.lines().next().map(str::trim) {
+                    Some(line) if !line.is_empty() => Ok(line.to_owned()),
+                    _ => bail!("{:?} printed nothing", command),
+                }
+            }
+        }
+    }
+}
+
+/// `deserialize_with` for secrets that may be given as a [`Source`].
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Source::deserialize(d)?
+        .read()
+        .map_err(|e| de::Error::custom(format!("{:#}", e)))
+}
+
+/// Moves the `api` session of a state dump into the keyring.
+pub fn seal_state(state: &mut Value) -> Result<()> {
+    let Some(api) = state.as_object_mut().and_then(|m| m.get_mut("api")) else {
+        bail!("missing api");
+    };
+    if *api != MARKER {
+        set(SESSION, &serde_json::to_string(api)?)?;
+        *api = Value::from(MARKER);
+    }
+    Ok(())
+}
+
+/// Puts the session back into a state dump sealed by [`seal_state`].
+pub fn unseal_state(state: &mut Value) -> Result<()> {
+    if let Some(api) = state.as_object_mut().and_then(|m| m.get_mut("api")) {
+        if *api == MARKER {
+            *api = serde_json::from_str(&get(SESSION)?).context("keyring session")?;
+        }
+    }
+    Ok(())
+}
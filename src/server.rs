@@ -0,0 +1,792 @@
+//! A tiny HTTP endpoint for hardware buttons and scripts.
+//!
+//! - `GET /`: a dashboard with the count, the unread illusts, the buttons of `/refresh` and
+//!   `/mark-read`, and a chart of `/history`.
+//! - `GET /count`: the display count as plain text, e.g. `42` or `300+`.
+//! - `GET /status`: the full [`Status`] as JSON.
+//! - `GET /key.png`: the count rendered on a 72x72 PNG for Stream Deck keys.
+//! - `GET /export?format=csv`: the unread illusts as JSON (the default) or CSV.
+//! - `GET /thumb?id=N`: the cached thumbnail of an unread illust, if `thumbs` is configured,
+//!   blurred per `blur_sanity_level`.
+//! - `GET /history?days=N`: the `event_log` entries of the last `N` days (7 by default) as
+//!   JSON, if it is configured.
+//! - `POST /refresh`: triggers an immediate refresh; no body is needed.
+//! - `POST /snooze?minutes=N`: suppresses notifications for `N` minutes (60 by default), `0`
+//!   resumes them early.
+//! - `POST /metered?on=1`: switches the low-bandwidth mode on or off, toggles it without `on`.
+//! - `POST /mark-read`: zeroes the count in the `mark_read` and `hybrid` anchor modes.
+//! - `POST /open?id=N`: opens an unread illust, the newest without `id`, in the browser of the
+//!   machine running the daemon.
+//! - `POST /reload`: re-reads `config.json`; the HTTP listener itself is kept.
+//! - `POST /shutdown`: saves the state and exits, used by `--takeover`.
+//! - `GET /api/sensor`: state and attributes for a Home Assistant RESTful sensor,
+//!   see `illust-notify hass` for the matching YAML.
+//! - `GET /events`: Server-Sent Events, `count` with the [`Status`] as JSON whenever the count
+//!   or the anchor changes, `error` with the message as a JSON string, and `heartbeat` every
+//!   15s; e.g. `curl -N http://127.0.0.1:8977/events`.
+//! - `GET /ws`: the same events over a WebSocket, see [`crate::ws`].
+//!
+//! Requests a browser sends from a page of another origin are refused, as bodyless `POST`s
+//! need no CORS preflight, and so are those naming a `Host` other than this machine's
+//! addresses, the `listen` host and `hosts`, which a DNS rebinding page would. `POST`s also
+//! need `Authorization: Bearer <token>` if `token` is set, and are only taken from this machine
+//! otherwise.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use illust_notify::process;
+use illust_notify::thumbs::ThumbsConfig;
+use illust_notify::{ApiUsage, Illust, Tracker};
+use pixiv::model::IllustId;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::badge;
+use crate::browse;
+use crate::eventlog::EventLogConfig;
+use crate::export::{self, Format};
+use crate::wake::{self, Wake};
+use crate::ws;
+
+const KEY_SIZE: usize = 72;
+const DASHBOARD: &str = include_str!("dashboard.html");
+/// For the request head, so a client that sends nothing lets go of its task.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the commands talking to a running daemon wait for each step.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Keeps proxies from closing an idle `/events` stream, and notices clients gone.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HttpConfig {
+    /// Address to bind, e.g. `127.0.0.1:8977`.
+    listen: String,
+    /// Required as `Authorization: Bearer <token>` on `POST`s, which are otherwise only
+    /// taken from this machine. Read at startup, like `listen`.
+    token: Option<String>,
+    /// Further names the daemon is reached by, e.g. `nas.local`.
+    #[serde(default)]
+    hosts: Vec<String>,
+    #[serde(default = "illust_notify::process::default_blur")]
+    pub blur: bool,
+}
+
+/// What [`authorize`] checks requests against, from [`HttpConfig`].
+struct Access {
+    token: Option<String>,
+    /// Lowercase names allowed as `Host` besides `localhost` and addresses.
+    hosts: Vec<String>,
+}
+
+impl Access {
+    fn new(config: &HttpConfig) -> Self {
+        let listen = strip_port(&config.listen);
+        let hosts = config.hosts.iter().map(String::as_str).chain([listen]);
+        Self {
+            token: config.token.clone(),
+            hosts: hosts.map(str::to_ascii_lowercase).collect(),
+        }
+    }
+}
+
+/// Snapshot of the counter published after every refresh.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Status {
+    pub count: usize,
+    pub count_text: String,
+    pub severity: String,
+    pub iid: IllustId,
+    pub since: String,
+    pub ago: String,
+    pub remain: bool,
+    pub skip: bool,
+    pub error: Option<String>,
+    /// Unread illusts posted within `window_hours`, if set.
+    #[serde(default)]
+    pub window: Option<usize>,
+    /// API calls in the last hour and day.
+    #[serde(default)]
+    pub api: ApiUsage,
+    /// Counts of the tag searches by search word.
+    #[serde(default)]
+    pub searches: BTreeMap<String, usize>,
+    /// Kept for `/export`, `/status` stays small.
+    #[serde(skip)]
+    pub unread: Vec<Illust>,
+}
+
+#[derive(Serialize, Debug)]
+struct Sensor<'a> {
+    state: usize,
+    attributes: SensorAttributes<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct SensorAttributes<'a> {
+    count_text: &'a str,
+    severity: &'a str,
+    anchor: IllustId,
+    since: &'a str,
+    ago: &'a str,
+    remain: bool,
+    skip: bool,
+    error: Option<&'a str>,
+}
+
+const SENSOR_ATTRIBUTES: &[&str] = &[
+    "count_text",
+    "severity",
+    "anchor",
+    "since",
+    "ago",
+    "remain",
+    "skip",
+    "error",
+];
+
+impl Status {
+    pub fn new(app: &Tracker) -> Self {
+        Self {
+            count: app.dist(),
+            count_text: app.dist_text(),
+            severity: app.severity().as_str().to_owned(),
+            iid: app.iid,
+            since: app.since(),
+            ago: app.since_ago(),
+            remain: app.remain,
+            skip: app.skip,
+            error: None,
+            window: app.window_count(),
+            api: app.api_usage(),
+            searches: app
+                .searches
+                .iter()
+                .map(|(word, s)| (word.clone(), s.count))
+                .collect(),
+            unread: app.unread.clone(),
+        }
+    }
+
+    /// One line for shell prompts, e.g. `42 since 2024-01-02 03:04 (3 hours ago) #123`.
+    pub fn line(&self) -> String {
+        let mut r = format!(
+            "{} since {} ({}) #{}",
+            self.count_text, self.since, self.ago, self.iid
+        );
+        if let Some(e) = &self.error {
+            r += &format!(" !{}", e);
+        }
+        r
+    }
+
+    fn sensor(&self) -> Sensor<'_> {
+        Sensor {
+            state: self.count,
+            attributes: SensorAttributes {
+                count_text: &self.count_text,
+                severity: &self.severity,
+                anchor: self.iid,
+                since: &self.since,
+                ago: &self.ago,
+                remain: self.remain,
+                skip: self.skip,
+                error: self.error.as_deref(),
+            },
+        }
+    }
+}
+
+pub struct Shared {
+    pub status: watch::Receiver<Status>,
+    pub wake: wake::Sender,
+    pub thumbs: Option<ThumbsConfig>,
+    /// `blur_sanity_level`, unless `blur` is off.
+    pub blur_sanity_level: Option<u32>,
+    pub event_log: Option<EventLogConfig>,
+}
+
+struct Response {
+    code: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn text(code: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            code,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into().into_bytes(),
+        }
+    }
+}
+
+fn query<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .find_map(|(k, v)| (k == key).then_some(v))
+}
+
+/// The value of the header `name` in a request head.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.split("\r\n")
+        .skip(1)
+        .take_while(|l| !l.is_empty())
+        .filter_map(|l| l.split_once(':'))
+        .find_map(|(k, v)| k.trim().eq_ignore_ascii_case(name).then(|| v.trim()))
+}
+
+/// `host:port` without the port.
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((h, port)) if !h.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => h,
+        _ => host,
+    }
+}
+
+/// Whether an `Origin` is a page served by this machine.
+fn local_origin(origin: &str) -> bool {
+    let Some((_, host)) = origin.split_once("://") else {
+        return false;
+    };
+    matches!(strip_port(host), "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// Whether a `Host` names this machine, `local` being the address the request came in on.
+fn local_host(host: &str, local: IpAddr, hosts: &[String]) -> bool {
+    let host = strip_port(host);
+    let ip = host.trim_start_matches('[').trim_end_matches(']');
+    match ip.parse::<IpAddr>() {
+        // Rebinding needs a name, any address reached is fine.
+        Ok(ip) => ip.is_loopback() || ip == local,
+        Err(_) => {
+            let host = host.to_ascii_lowercase();
+            host == "localhost" || hosts.contains(&host)
+        }
+    }
+}
+
+/// The refusal for a request, if it gets one.
+fn authorize(
+    method: &str,
+    head: &str,
+    peer: IpAddr,
+    local: IpAddr,
+    access: &Access,
+) -> Option<Response> {
+    let host = header(head, "host");
+    // Pages served here, like the dashboard, send their own origin, which the host is checked for.
+    let same = |o: &str| o.split_once("://").map(|(_, h)| h) == host;
+    if header(head, "origin").is_some_and(|o| !local_origin(o) && !same(o)) {
+        return Some(Response::text("403 Forbidden", "foreign origin\n"));
+    }
+    if host.is_some_and(|h| !local_host(h, local, &access.hosts)) {
+        return Some(Response::text(
+            "403 Forbidden",
+            "unknown host, add it to http.hosts\n",
+        ));
+    }
+    if method != "POST" {
+        return None;
+    }
+    match access.token.as_deref() {
+        Some(token) => {
+            let given = header(head, "authorization").and_then(|a| a.strip_prefix("Bearer "));
+            (given != Some(token)).then(|| Response::text("401 Unauthorized", "invalid token\n"))
+        }
+        None if !peer.is_loopback() => Some(Response::text(
+            "403 Forbidden",
+            "set http.token to accept this from other machines\n",
+        )),
+        None => None,
+    }
+}
+
+/// The method, path and query of a request head.
+fn request_line(head: &str) -> (&str, &str, &str) {
+    let mut parts = head.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let (path, q) = path.split_once('?').unwrap_or((path, ""));
+    (method, path, q)
+}
+
+/// The end of a snooze of `minutes`, `None` past what the clock can represent.
+fn snooze_until(minutes: u64) -> Option<Instant> {
+    let secs = minutes.checked_mul(60)?;
+    Instant::now().checked_add(Duration::from_secs(secs))
+}
+
+fn route(method: &str, path: &str, q: &str, shared: &Shared) -> Result<Response> {
+    let status = shared.status.borrow().clone();
+    let text = match &status.error {
+        Some(_) => "!".to_owned(),
+        None => status.count_text.clone(),
+    };
+    Ok(match (method, path) {
+        ("GET", "/") => Response {
+            code: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body: DASHBOARD.as_bytes().to_vec(),
+        },
+        ("GET", "/count") => Response::text("200 OK", text + "\n"),
+        ("GET", "/status") => Response {
+            code: "200 OK",
+            content_type: "application/json",
+            body: serde_json::to_vec(&status)?,
+        },
+        ("GET", "/api/sensor") => Response {
+            code: "200 OK",
+            content_type: "application/json",
+            body: serde_json::to_vec(&status.sensor())?,
+        },
+        ("GET", "/key.png") => Response {
+            code: "200 OK",
+            content_type: "image/png",
+            body: badge::render(&text, KEY_SIZE),
+        },
+        ("GET", "/export") => match Format::parse(query(q, "format").unwrap_or("json")) {
+            Some(format) => Response {
+                code: "200 OK",
+                content_type: format.content_type(),
+                body: export::render(&status.unread, format)?.into_bytes(),
+            },
+            None => Response::text("400 Bad Request", "invalid format\n"),
+        },
+        ("GET", "/thumb") => {
+            let id = query(q, "id").and_then(|id| id.parse::<IllustId>().ok());
+            let path = id.zip(shared.thumbs.as_ref()).map(|(id, t)| t.path(id));
+            let illust = id.and_then(|id| status.unread.iter().find(|i| i.id == id));
+            let blur = illust
+                .is_some_and(|i| shared.blur_sanity_level.is_some_and(|n| i.sanity_level > n));
+            let path = match path {
+                Some(p) if blur => process::blurred(&p).ok(),
+                p => p,
+            };
+            match path.map(fs::read) {
+                Some(Ok(body)) => Response {
+                    code: "200 OK",
+                    content_type: "image/jpeg",
+                    body,
+                },
+                _ => Response::text("404 Not Found", "not cached\n"),
+            }
+        }
+        ("GET", "/history") => match &shared.event_log {
+            Some(log) => {
+                let days = query(q, "days").and_then(|d| d.parse().ok()).unwrap_or(7);
+                // Everything when that is further back than the clock goes.
+                let from = OffsetDateTime::now_utc().checked_sub(time::Duration::days(days));
+                let mut entries = log.read()?;
+                if let Some(from) = from {
+                    entries.retain(|e| {
+                        OffsetDateTime::parse(&e.time, &Rfc3339).is_ok_and(|t| t >= from)
+                    });
+                }
+                Response {
+                    code: "200 OK",
+                    content_type: "application/json",
+                    body: serde_json::to_vec(&entries)?,
+                }
+            }
+            None => Response::text("404 Not Found", "event_log is not configured\n"),
+        },
+        ("POST", "/refresh") => {
+            shared.wake.send(Wake::ManualRefresh)?;
+            Response::text("202 Accepted", "refreshing\n")
+        }
+        ("POST", "/snooze") => match query(q, "minutes").unwrap_or("60").parse::<u64>() {
+            Ok(0) => {
+                shared.wake.send(Wake::Snooze(None))?;
+                Response::text("200 OK", "resumed\n")
+            }
+            Ok(m) => match snooze_until(m) {
+                Some(until) => {
+                    shared.wake.send(Wake::Snooze(Some(until)))?;
+                    Response::text("200 OK", format!("snoozed for {} minutes\n", m))
+                }
+                None => Response::text("400 Bad Request", "too many minutes\n"),
+            },
+            Err(_) => Response::text("400 Bad Request", "invalid minutes\n"),
+        },
+        ("POST", "/metered") => {
+            let on = query(q, "on").map(|v| v == "1" || v == "true");
+            shared.wake.send(Wake::Metered(on))?;
+            let text = match on {
+                Some(true) => "metered on\n",
+                Some(false) => "metered off\n",
+                None => "metered toggled\n",
+            };
+            Response::text("200 OK", text)
+        }
+        ("POST", "/reload") => {
+            shared.wake.send(Wake::ConfigReload)?;
+            Response::text("202 Accepted", "reloading\n")
+        }
+        ("POST", "/mark-read") => {
+            shared.wake.send(Wake::MarkRead)?;
+            Response::text("202 Accepted", "marking read\n")
+        }
+        ("POST", "/open") => {
+            let id = match query(q, "id") {
+                Some(id) => id.parse().ok(),
+                None => status.unread.first().map(|i| i.id),
+            };
+            match id {
+                Some(id) => match browse::illust(id, &shared.wake) {
+                    Ok(()) => Response::text("202 Accepted", "opening\n"),
+                    Err(e) => Response::text("500 Internal Server Error", format!("{:#}\n", e)),
+                },
+                None => Response::text("404 Not Found", "nothing to open\n"),
+            }
+        }
+        ("POST", "/shutdown") => {
+            shared.wake.send(Wake::Shutdown)?;
+            Response::text("202 Accepted", "shutting down\n")
+        }
+        (
+            _,
+            "/" | "/count" | "/status" | "/api/sensor" | "/key.png" | "/export" | "/thumb"
+            | "/history" | "/refresh" | "/snooze" | "/metered" | "/reload" | "/mark-read" | "/open"
+            | "/shutdown" | "/events" | "/ws",
+        ) => Response::text("405 Method Not Allowed", "method not allowed\n"),
+        _ => Response::text("404 Not Found", "not found\n"),
+    })
+}
+
+/// What a pushed stream has been told so far, see [`Pushed::changes`].
+#[derive(Default)]
+pub struct Pushed {
+    token: Option<(IllustId, String)>,
+    error: Option<String>,
+}
+
+impl Pushed {
+    /// The events that bring a subscriber up to `status`, as names and JSON data.
+    pub fn changes(&mut self, status: &Status) -> Result<Vec<(&'static str, String)>> {
+        let mut r = Vec::new();
+        if self.error != status.error {
+            self.error.clone_from(&status.error);
+            if let Some(e) = &status.error {
+                r.push(("error", serde_json::to_string(e)?));
+            }
+        }
+        let token = (status.iid, status.count_text.clone());
+        if self.token.as_ref() != Some(&token) {
+            self.token = Some(token);
+            r.push(("count", serde_json::to_string(status)?));
+        }
+        Ok(r)
+    }
+}
+
+/// Streams `/events` until the client goes away or the daemon shuts down.
+async fn events(mut stream: TcpStream, shared: &Shared) -> Result<()> {
+    // Nginx buffers responses unless told otherwise.
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+              X-Accel-Buffering: no\r\nConnection: close\r\n\r\n",
+        )
+        .await?;
+    let mut status = shared.status.clone();
+    let mut pushed = Pushed::default();
+    loop {
+        let current = status.borrow_and_update().clone();
+        for (event, data) in pushed.changes(&current)? {
+            let msg = format!("event: {}\ndata: {}\n\n", event, data);
+            stream.write_all(msg.as_bytes()).await?;
+        }
+        tokio::select! {
+            r = status.changed() => r?,
+            _ = sleep(HEARTBEAT) => stream.write_all(b"event: heartbeat\ndata: {}\n\n").await?,
+        }
+    }
+}
+
+async fn read_head(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0; 1024];
+    // Only the request line and headers matter; bodies are ignored.
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("connection closed");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > 8192 {
+            bail!("request too large");
+        }
+    }
+    Ok(buf)
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    shared: &Shared,
+    access: &Access,
+) -> Result<()> {
+    let local = stream.local_addr()?.ip();
+    let Ok(buf) = timeout(READ_TIMEOUT, read_head(&mut stream)).await else {
+        bail!("no request within {}s", READ_TIMEOUT.as_secs());
+    };
+    let head = String::from_utf8_lossy(&buf?).into_owned();
+    let (method, path, q) = request_line(&head);
+    debug!("http: {} {}", method, path);
+
+    let r = match authorize(method, &head, peer.ip(), local, access) {
+        None if (method, path) == ("GET", "/events") => return events(stream, shared).await,
+        None if (method, path) == ("GET", "/ws") => match header(&head, "sec-websocket-key") {
+            Some(key) => return ws::serve(stream, key, shared.status.clone()).await,
+            None => Response::text("400 Bad Request", "not a WebSocket handshake\n"),
+        },
+        Some(r) => {
+            warn!(
+                "http: refused {} {} from {}: {}",
+                method, path, peer, r.code
+            );
+            r
+        }
+        None => route(method, path, q, shared)?,
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        r.code,
+        r.content_type,
+        r.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&r.body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// A `configuration.yaml` snippet for a RESTful sensor polling `/api/sensor`.
+pub fn hass_yaml(config: &HttpConfig, host: Option<&str>, scan_interval: u32) -> String {
+    let (addr, port) = config
+        .listen
+        .rsplit_once(':')
+        .unwrap_or((&config.listen, "80"));
+    let host = match host {
+        Some(host) => host,
+        None if addr == "0.0.0.0" || addr == "[::]" => "DAEMON_HOST",
+        None => addr,
+    };
+    let attributes: String = SENSOR_ATTRIBUTES
+        .iter()
+        .map(|a| format!("      - {}\n", a))
+        .collect();
+    format!(
+        "sensor:
+  - platform: rest
+    name: Pixiv unread illusts
+    unique_id: illust_notify_unread
+    resource: http://{host}:{port}/api/sensor
+    scan_interval: {scan_interval}
+    unit_of_measurement: illusts
+    value_template: \"{{{{ value_json.state }}}}\"
+    json_attributes_path: \"$.attributes\"
+    json_attributes:
+{attributes}"
+    )
+}
+
+/// Sends a bodyless `POST` to a running daemon and returns the response body.
+pub fn post(config: &HttpConfig, path: &str) -> Result<String> {
+    request(config, "POST", path)
+}
+
+/// Sends a `GET` to a running daemon and returns the response body.
+pub fn get(config: &HttpConfig, path: &str) -> Result<String> {
+    request(config, "GET", path)
+}
+
+/// Connects to the first address of `addr` that answers, bounding every step by
+/// [`CLIENT_TIMEOUT`] so a wedged daemon does not hang the command.
+fn connect(addr: &str) -> Result<std::net::TcpStream> {
+    let mut last = None;
+    for a in addr.to_socket_addrs()? {
+        match std::net::TcpStream::connect_timeout(&a, CLIENT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+                stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(e) => last = Some(e),
+        }
+    }
+    match last {
+        Some(e) => Err(e).with_context(|| format!("connect to {}", addr)),
+        None => bail!("{} resolves to no address", addr),
+    }
+}
+
+fn request(config: &HttpConfig, method: &str, path: &str) -> Result<String> {
+    let addr = match config.listen.rsplit_once(':') {
+        Some(("0.0.0.0", port)) => format!("127.0.0.1:{}", port),
+        Some(("[::]", port)) => format!("[::1]:{}", port),
+        _ => config.listen.clone(),
+    };
+    let auth = match &config.token {
+        Some(token) => format!("Authorization: Bearer {}\r\n", token),
+        None => String::new(),
+    };
+    let mut stream = connect(&addr)?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        method, path, addr, auth
+    )?;
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp)?;
+    let (head, body) = resp.split_once("\r\n\r\n").unwrap_or((&resp, ""));
+    let code = head.split_whitespace().nth(1).unwrap_or_default();
+    if !code.starts_with('2') {
+        bail!("{}: {}", code, body.trim());
+    }
+    Ok(body.to_owned())
+}
+
+/// Binds the listener and serves requests in the background.
+pub async fn spawn(config: &HttpConfig, shared: Shared) -> Result<()> {
+    let listener = TcpListener::bind(config.listen.as_str()).await?;
+    info!("http: listening on {}", listener.local_addr()?);
+    let shared = Arc::new(shared);
+    let access = Arc::new(Access::new(config));
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    let shared = shared.clone();
+                    let access = access.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle(stream, addr, &shared, &access).await {
+                            debug!("http: {}: {:#}", addr, e);
+                        }
+                    });
+                }
+                Err(e) => error!("http: accept: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    const LOCAL: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const REMOTE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+
+    #[test]
+    fn request_lines() {
+        let head = "GET /export?format=csv HTTP/1.1\r\nHost: x\r\n\r\n";
+        assert_eq!(request_line(head), ("GET", "/export", "format=csv"));
+        assert_eq!(
+            request_line("POST /refresh HTTP/1.1"),
+            ("POST", "/refresh", "")
+        );
+        assert_eq!(request_line(""), ("", "", ""));
+    }
+
+    #[test]
+    fn headers() {
+        let head = "GET / HTTP/1.1\r\nHost: a\r\nORIGIN:  http://localhost \r\n\r\nOrigin: b";
+        assert_eq!(header(head, "origin"), Some("http://localhost"));
+        assert_eq!(header(head, "host"), Some("a"));
+        // Neither the request line nor the body is a header.
+        assert_eq!(header("Origin: x / HTTP/1.1\r\n\r\n", "origin"), None);
+        assert_eq!(header(head, "authorization"), None);
+    }
+
+    #[test]
+    fn local_origins() {
+        assert!(local_origin("http://localhost"));
+        assert!(local_origin("http://127.0.0.1:8080"));
+        assert!(local_origin("http://[::1]:80"));
+        assert!(!local_origin("http://localhost.example.com"));
+        assert!(!local_origin("https://example.com"));
+        assert!(!local_origin("null"));
+    }
+
+    #[test]
+    fn authorizes() {
+        let code = |method, head, peer, token: Option<&str>| {
+            let access = Access {
+                token: token.map(Into::into),
+                hosts: Vec::new(),
+            };
+            authorize(method, head, peer, LOCAL, &access).map(|r| r.code)
+        };
+        let plain = "POST /refresh HTTP/1.1\r\n\r\n";
+        let foreign = "GET /count HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n";
+        let bearer = "POST /refresh HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n";
+
+        assert_eq!(code("GET", foreign, LOCAL, None), Some("403 Forbidden"));
+        assert_eq!(
+            code("GET", "GET /count HTTP/1.1\r\n\r\n", REMOTE, None),
+            None
+        );
+        assert_eq!(code("POST", plain, LOCAL, None), None);
+        assert_eq!(
+            code("POST", plain, IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            None
+        );
+        assert_eq!(code("POST", plain, REMOTE, None), Some("403 Forbidden"));
+        assert_eq!(
+            code("POST", plain, LOCAL, Some("s3cret")),
+            Some("401 Unauthorized")
+        );
+        assert_eq!(code("POST", bearer, REMOTE, Some("s3cret")), None);
+        assert_eq!(
+            code("POST", bearer, REMOTE, Some("other")),
+            Some("401 Unauthorized")
+        );
+    }
+
+    #[test]
+    fn local_hosts() {
+        let lan = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5));
+        let hosts = ["nas.local".to_owned()];
+        assert!(local_host("localhost:8977", lan, &hosts));
+        assert!(local_host("127.0.0.1:8977", lan, &hosts));
+        assert!(local_host("[::1]:8977", lan, &hosts));
+        assert!(local_host("192.168.1.5:8977", lan, &hosts));
+        assert!(local_host("NAS.local", lan, &hosts));
+        assert!(!local_host("192.168.1.6:8977", lan, &hosts));
+        assert!(!local_host("rebind.example.com:8977", lan, &hosts));
+
+        let access = Access {
+            token: None,
+            hosts: Vec::new(),
+        };
+        let rebound = "GET /status HTTP/1.1\r\nHost: rebind.example.com:8977\r\n\r\n";
+        let code = authorize("GET", rebound, LOCAL, LOCAL, &access).map(|r| r.code);
+        assert_eq!(code, Some("403 Forbidden"));
+        let dashboard = "POST /refresh HTTP/1.1\r\nHost: 192.168.1.5:8977\r\n\
+                         Origin: http://192.168.1.5:8977\r\n\r\n";
+        assert!(authorize("POST", dashboard, LOCAL, lan, &access).is_none());
+    }
+
+    #[test]
+    fn snooze_overflow() {
+        assert!(snooze_until(30).is_some());
+        assert!(snooze_until(u64::MAX).is_none());
+        assert!(snooze_until(u64::MAX / 60).is_none());
+    }
+}
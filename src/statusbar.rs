@@ -0,0 +1,97 @@
+use std::fs;
+use std::io::Write;
+
+use anyhow::Result;
+use illust_notify::Tracker;
+use serde::{Deserialize, Serialize};
+
+use crate::callback::{Event, Payload};
+use crate::template;
+
+fn default_urgent() -> usize {
+    50
+}
+
+fn default_max() -> usize {
+    100
+}
+
+/// Waybar-style JSON output (also understood by i3blocks and polybar scripts).
+#[derive(Deserialize, Debug, Clone)]
+pub struct StatusbarConfig {
+    /// File to write, or `-` for one JSON line per refresh on stdout.
+    path: String,
+    /// Count from which the `urgent` class is set.
+    #[serde(default = "default_urgent")]
+    urgent: usize,
+    /// Count that maps to 100 percent.
+    #[serde(default = "default_max")]
+    max: usize,
+    /// Templates for the text and tooltip, see [`crate::template`].
+    text: Option<String>,
+    tooltip: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Output<'a> {
+    text: &'a str,
+    tooltip: &'a str,
+    class: Vec<&'a str>,
+    percentage: usize,
+}
+
+impl StatusbarConfig {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn update(&self, app: &Tracker) -> Result<()> {
+        let dist = app.dist();
+        let level = if dist == 0 {
+            "empty"
+        } else if dist >= self.urgent {
+            "urgent"
+        } else {
+            "unread"
+        };
+        let payload = Payload::new(app, Event::Refresh);
+        let tooltip = match &self.tooltip {
+            Some(t) => template::render(t, &payload),
+            None => app.summary(),
+        };
+        let text = match &self.text {
+            Some(t) => template::render(t, &payload),
+            None => app.dist_text(),
+        };
+        self.write(&Output {
+            text: &text,
+            tooltip: &tooltip,
+            class: vec![level, app.severity().as_str()],
+            percentage: (dist * 100 / self.max.max(1)).min(100),
+        })
+    }
+
+    pub fn error(&self, e: &anyhow::Error) -> Result<()> {
+        self.write(&Output {
+            text: "!",
+            tooltip: &format!("{:#}", e),
+            class: vec!["error"],
+            percentage: 0,
+        })
+    }
+
+    fn write(&self, out: &Output) -> Result<()> {
+        let s = serde_json::to_string(out)?;
+        if self.path == "-" {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", s)?;
+            stdout.flush()?;
+        } else {
+            // Replace atomically so pollers never read a partial object.
+            let tmp = format!("{}.tmp", self.path);
+            fs::write(&tmp, s)?;
+            fs::rename(&tmp, &self.path)?;
+        }
+        Ok(())
+    }
+}
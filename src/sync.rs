@@ -0,0 +1,129 @@
+//! Shares the anchor and the visited set between machines through a file on a WebDAV server.
+//!
+//! Each poll compares the `updated` time of the remote file with when the local anchor last
+//! moved; the newer side wins, so the last machine to bookmark or mark read decides. While the
+//! server is unreachable the local state keeps working and is pushed once it is back.
+
+use std::env;
+
+use anyhow::Result;
+use illust_notify::{SharedState, Tracker};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::time::{Duration, Instant};
+
+use crate::secrets;
+
+fn default_interval() -> u64 {
+    300
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SyncConfig {
+    /// URL of the shared file, e.g. `https://dav.example.org/illust-notify/shared.json`.
+    url: String,
+    user: Option<String>,
+    /// Or `"keyring"` for the `sync` account.
+    password: Option<String>,
+    /// Seconds between polls.
+    #[serde(default = "default_interval")]
+    interval: u64,
+    /// This machine in the shared file, `$HOSTNAME` if missing.
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Remote {
+    updated: OffsetDateTime,
+    host: String,
+    #[serde(flatten)]
+    state: SharedState,
+}
+
+pub struct Sync {
+    client: reqwest::Client,
+    next: Option<Instant>,
+}
+
+impl Sync {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            next: None,
+        }
+    }
+
+    /// When the next poll is due.
+    pub fn next(&self) -> Instant {
+        self.next.unwrap_or_else(Instant::now)
+    }
+
+    fn request(
+        &self,
+        config: &SyncConfig,
+        req: reqwest::RequestBuilder,
+        timeout: Duration,
+    ) -> Result<reqwest::RequestBuilder> {
+        let req = req.timeout(timeout);
+        Ok(match &config.user {
+            Some(user) => {
+                let password = match &config.password {
+                    Some(p) => Some(secrets::resolve(p, secrets::SYNC)?),
+                    None => None,
+                };
+                req.basic_auth(user, password)
+            }
+            None => req,
+        })
+    }
+
+    /// Pulls or pushes the shared state, returning whether the remote one was adopted.
+    pub async fn poll(&mut self, app: &mut Tracker, config: &SyncConfig) -> Result<bool> {
+        self.next = Some(Instant::now() + Duration::from_secs(config.interval));
+        let timeout = app.request_timeout();
+        let r = self
+            .request(config, self.client.get(&config.url), timeout)?
+            .send()
+            .await?;
+        let remote: Option<Remote> = if r.status() == StatusCode::NOT_FOUND {
+            None
+        } else {
+            Some(r.error_for_status()?.json().await?)
+        };
+
+        let local = app.changed_at.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        match remote {
+            Some(remote) if remote.updated > local => {
+                let moved = (remote.state.iid, remote.state.mark) != (app.iid, app.mark);
+                debug!("sync: {} is newer", remote.host);
+                if moved {
+                    app.adopt(remote.state, remote.updated);
+                } else {
+                    app.changed_at = Some(remote.updated);
+                }
+                Ok(moved)
+            }
+            Some(remote) if remote.updated == local => Ok(false),
+            _ => {
+                let body = Remote {
+                    updated: local,
+                    host: config
+                        .name
+                        .clone()
+                        .or_else(|| env::var("HOSTNAME").ok())
+                        .unwrap_or_default(),
+                    state: app.shared(),
+                };
+                self.request(config, self.client.put(&config.url), timeout)?
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_vec(&body)?)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                debug!("sync: pushed anchor {}", app.iid);
+                Ok(false)
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! `{name}` placeholders in user-visible messages, e.g. `"{count_text} new: {title} by {artist}"`.
+//!
+//! Variables: `count`, `count_text`, `severity`, `since`, `ago`, `iid`, `remain`, `skip`, `new`,
+//! `window` (the `window_hours` count), `title`, `artist` and `caption` of the newest unread
+//! illust, `anchor_title` and `anchor_artist` of the bookmarked one, and the `message` of
+//! non-count events. `{{` and `}}` produce literal braces, unknown names are kept as they are.
+
+use crate::callback::Payload;
+
+fn var(payload: &Payload, name: &str) -> Option<String> {
+    Some(match name {
+        "count" => payload.count.to_string(),
+        "count_text" => payload.count_text.clone(),
+        "severity" => payload.severity.to_owned(),
+        "since" => payload.since.clone(),
+        "ago" => payload.ago.clone(),
+        "iid" => payload.iid.to_string(),
+        "remain" => payload.remain.to_string(),
+        "skip" => payload.skip.to_string(),
+        "new" => payload.new_since_alert.to_string(),
+        "window" => payload.window.map(|n| n.to_string()).unwrap_or_default(),
+        "title" => payload.title.clone(),
+        "artist" => payload.artist.clone(),
+        "caption" => payload.caption.clone(),
+        "anchor_title" => payload.anchor_title.clone(),
+        "anchor_artist" => payload.anchor_artist.clone(),
+        "message" => payload.message.clone().unwrap_or_default(),
+        _ => return None,
+    })
+}
+
+pub fn render(tpl: &str, payload: &Payload) -> String {
+    let mut r = String::with_capacity(tpl.len());
+    let mut rest = tpl;
+    while let Some(i) = rest.find(['{', '}']) {
+        r.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            r.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let name = tail[1..].split_once('}').map(|(n, _)| n);
+        match name
+            .filter(|_| tail.starts_with('{'))
+            .and_then(|n| Some((n, var(payload, n)?)))
+        {
+            Some((n, v)) => {
+                r.push_str(&v);
+                rest = &tail[n.len() + 2..];
+            }
+            None => {
+                r.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    r.push_str(rest);
+    r
+}
@@ -0,0 +1,219 @@
+//! A built-in Termux backend using the Termux:API commands, for phones without a callback script.
+
+use std::process::Command;
+
+use anyhow::{bail, Result};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use illust_notify::lang::Messages;
+use illust_notify::Tracker;
+use serde::Deserialize;
+
+use crate::callback::{Event, Payload};
+use crate::notifier::Notifier;
+use crate::template;
+
+fn default_id() -> String {
+    "illust-notify".to_owned()
+}
+
+fn default_events() -> Vec<Event> {
+    vec![
+        Event::Refresh,
+        Event::Error,
+        Event::ReauthNeeded,
+        Event::Keyword,
+        Event::Outage,
+        Event::Gap,
+        Event::Ranking,
+        Event::Search,
+        Event::Digest,
+        Event::Pixivision,
+        Event::Fanbox,
+        Event::Update,
+        Event::Status,
+    ]
+}
+
+fn default_wake_lock() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TermuxConfig {
+    /// Notification id, refreshes replace the notification with the same id.
+    #[serde(default = "default_id")]
+    id: String,
+    #[serde(default = "default_events")]
+    events: Vec<Event>,
+    /// Also show a toast for new alerts.
+    #[serde(default)]
+    toast: bool,
+    /// Templates for the notification, see [`crate::template`].
+    title: Option<String>,
+    body: Option<String>,
+    /// Hold `termux-wake-lock` while the daemon runs so Android does not suspend it.
+    #[serde(default = "default_wake_lock")]
+    wake_lock: bool,
+    #[serde(default = "illust_notify::process::default_blur")]
+    blur: bool,
+}
+
+pub struct Termux {
+    config: TermuxConfig,
+    errored: bool,
+}
+
+/// Runs a Termux:API command, which can hang when the Termux:API app is missing.
+fn run(args: &[&str]) -> Result<()> {
+    debug!("termux: {:?}", args);
+    let r = Command::new("timeout").arg("30").args(args).status()?;
+    if !r.success() {
+        bail!("{} returned {:?}", args[0], r.code());
+    }
+    Ok(())
+}
+
+impl Termux {
+    pub fn new(config: TermuxConfig) -> Self {
+        Self {
+            config,
+            errored: false,
+        }
+    }
+
+    fn count(&mut self, payload: &Payload, m: &Messages) -> Result<()> {
+        let err_id = format!("{}-err", self.config.id);
+        if self.errored {
+            run(&["termux-notification-remove", &err_id])?;
+            self.errored = false;
+        }
+
+        let title = match &self.config.title {
+            Some(t) => template::render(t, payload),
+            None => m.count(&payload.count_text),
+        };
+        let content = if let Some(t) = &self.config.body {
+            template::render(t, payload)
+        } else {
+            let mut content = m.since(&payload.since, &payload.ago);
+            if payload.new_since_alert > 0 {
+                content += &m.new_since_alert(payload.new_since_alert);
+            }
+            if !payload.title.is_empty() {
+                content += &format!("\n{}", m.by(&payload.title, &payload.artist));
+            }
+            content
+        };
+        let action = format!(
+            "am start -a android.intent.action.VIEW -d https://www.pixiv.net/i/{}",
+            payload.iid
+        );
+        let mut args = vec![
+            "termux-notification",
+            "-i",
+            &self.config.id,
+            "-t",
+            &title,
+            "-c",
+            &content,
+            "--icon",
+            "favorite",
+            "--ongoing",
+            "--action",
+            &action,
+        ];
+        let image = payload
+            .image_for(self.config.blur)
+            .map(|p| p.display().to_string());
+        if let Some(image) = &image {
+            args.extend(["--image-path", image]);
+        }
+        // Only real alerts make a sound, plain refreshes update the notification silently.
+        if payload.event != Event::CountChanged && !payload.reminder {
+            args.push("--alert-once");
+        }
+        run(&args)?;
+
+        if self.config.toast && payload.event == Event::CountChanged {
+            run(&["termux-toast", &title])?;
+        }
+        Ok(())
+    }
+
+    fn error(&mut self, payload: &Payload) -> Result<()> {
+        let id = format!("{}-err", self.config.id);
+        let title = format!("illust-notify: {}", payload.event.as_str());
+        let message = payload.message.as_deref().unwrap_or_default();
+        run(&[
+            "termux-notification",
+            "-i",
+            &id,
+            "-t",
+            &title,
+            "-c",
+            message,
+            "--icon",
+            "error",
+        ])?;
+        self.errored = true;
+        Ok(())
+    }
+
+    /// A separate notification per event, so it is not replaced by the next refresh.
+    fn notice(&self, payload: &Payload, icon: &str, priority: &str) -> Result<()> {
+        let id = format!("{}-{}", self.config.id, payload.event.as_str());
+        let title = format!("illust-notify: {}", payload.event.as_str());
+        let message = payload.message.as_deref().unwrap_or_default();
+        run(&[
+            "termux-notification",
+            "-i",
+            &id,
+            "-t",
+            &title,
+            "-c",
+            message,
+            "--icon",
+            icon,
+            "--priority",
+            priority,
+        ])
+    }
+}
+
+impl Notifier for Termux {
+    fn name(&self) -> &str {
+        "termux"
+    }
+
+    fn accepts(&self, event: Event) -> bool {
+        let lifecycle = matches!(event, Event::Startup | Event::Shutdown);
+        self.config.events.contains(&event) || (self.config.wake_lock && lifecycle)
+    }
+
+    fn notify<'a>(
+        &'a mut self,
+        app: &'a Tracker,
+        payload: &'a Payload<'_>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        let subscribed = self.config.events.contains(&payload.event);
+        let r = match payload.event {
+            Event::Startup if self.config.wake_lock => run(&["termux-wake-lock"]),
+            Event::Shutdown if self.config.wake_lock => run(&["termux-wake-unlock"]),
+            _ if !subscribed => Ok(()),
+            Event::Refresh | Event::CountChanged => self.count(payload, app.messages()),
+            Event::Error | Event::ReauthNeeded | Event::Outage => self.error(payload),
+            Event::Keyword => self.notice(payload, "star", "high"),
+            Event::Gap => self.notice(payload, "info", "default"),
+            Event::Ranking | Event::Search | Event::Fanbox => {
+                self.notice(payload, "star", "default")
+            }
+            Event::Digest | Event::Pixivision | Event::Update => {
+                self.notice(payload, "info", "low")
+            }
+            Event::Status => self.notice(payload, "info", "default"),
+            Event::Startup | Event::Shutdown => Ok(()),
+        };
+        async move { r }.boxed_local()
+    }
+}
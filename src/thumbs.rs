@@ -0,0 +1,85 @@
+//! A bounded directory of `square_medium` thumbnails for the unread illusts, named `<id>.jpg`.
+//!
+//! The modification time of each file is its last use, so eviction drops the least recently
+//! used ones first until the directory fits in `max_mb`.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Result;
+use pixiv::model::IllustId;
+use serde::Deserialize;
+
+fn default_dir() -> PathBuf {
+    PathBuf::from("thumbs")
+}
+
+fn default_max_mb() -> u64 {
+    50
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ThumbsConfig {
+    /// Relative to the state directory.
+    #[serde(default = "default_dir")]
+    pub dir: PathBuf,
+    #[serde(default = "default_max_mb")]
+    pub max_mb: u64,
+}
+
+impl ThumbsConfig {
+    pub fn path(&self, id: IllustId) -> PathBuf {
+        self.dir.join(format!("{}.jpg", id))
+    }
+}
+
+/// Marks a cached thumbnail as just used, returning whether it exists.
+pub fn touch(path: &Path) -> bool {
+    match File::options().append(true).open(path) {
+        Ok(file) => {
+            if let Err(e) = file.set_modified(SystemTime::now()) {
+                debug!("touch {}: {}", path.display(), e);
+            }
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Removes the least recently used thumbnails until the directory is within `max_mb`.
+pub fn evict(config: &ThumbsConfig) -> Result<()> {
+    let mut files = Vec::new();
+    let mut total = 0;
+    let entries = match fs::read_dir(&config.dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if !meta.is_file() {
+            continue;
+        }
+        total += meta.len();
+        files.push((meta.modified()?, meta.len(), entry.path()));
+    }
+    let max = config.max_mb * 1024 * 1024;
+    if total <= max {
+        return Ok(());
+    }
+    files.sort_unstable_by_key(|(modified, ..)| *modified);
+    let mut removed = 0;
+    for (_, len, path) in files {
+        if total <= max {
+            break;
+        }
+        fs::remove_file(&path)?;
+        total -= len;
+        removed += 1;
+    }
+    debug!("evicted {} thumbnails", removed);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Spans of the requests and notifications of a refresh cycle, collected for an exporter.
+//!
+//! Nothing is recorded until [`enable`] is called, so the bounded requests in the library cost
+//! one atomic load each otherwise. The exporter drains the spans with [`take`] after each cycle.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use time::OffsetDateTime;
+
+/// Spans left over when no exporter drains them, e.g. after it failed repeatedly.
+const MAX_SPANS: usize = 1000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub name: String,
+    /// Unix time in nanoseconds.
+    pub start: i128,
+    pub end: i128,
+    pub ok: bool,
+    pub attrs: Vec<(&'static str, String)>,
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The current time as span timestamps take it.
+pub fn now() -> i128 {
+    OffsetDateTime::now_utc().unix_timestamp_nanos()
+}
+
+/// Records a span from `start` until now.
+pub fn record(name: &str, start: i128, ok: bool, attrs: Vec<(&'static str, String)>) {
+    if !enabled() {
+        return;
+    }
+    let span = Span {
+        name: name.to_owned(),
+        start,
+        end: now(),
+        ok,
+        attrs,
+    };
+    let mut spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+    if spans.len() < MAX_SPANS {
+        spans.push(span);
+    }
+}
+
+/// The spans recorded since the last call.
+pub fn take() -> Vec<Span> {
+    std::mem::take(&mut *SPANS.lock().unwrap_or_else(|e| e.into_inner()))
+}
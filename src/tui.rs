@@ -0,0 +1,194 @@
+//! `illust-notify tui`: the daemon in the foreground, showing the count, the unread illusts and
+//! the log instead of printing it.
+//!
+//! `r` refreshes, `m` marks read, `o` or Enter opens the selected illust in the browser, the
+//! arrows or `j`/`k` move and `q` quits. Everything else, from the backends to the HTTP
+//! endpoint, runs as in the daemon.
+
+use std::collections::VecDeque;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::Result;
+use log::{LevelFilter, Metadata, Record};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use time::{OffsetDateTime, UtcOffset};
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+use crate::browse;
+use crate::server::Status;
+use crate::wake::{self, Wake};
+
+const LOG_LINES: usize = 200;
+const TICK: Duration = Duration::from_millis(250);
+
+/// The last [`LOG_LINES`] log lines.
+#[derive(Clone, Default)]
+pub struct Log(Arc<Mutex<VecDeque<String>>>);
+
+impl Log {
+    fn lines(&self, n: usize) -> Vec<String> {
+        let lines = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+struct Capture {
+    log: Log,
+    level: LevelFilter,
+    tz: UtcOffset,
+}
+
+impl log::Log for Capture {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let t = OffsetDateTime::now_utc().to_offset(self.tz);
+        let line = format!(
+            "{:02}:{:02}:{:02} {:5} {}",
+            t.hour(),
+            t.minute(),
+            t.second(),
+            record.level(),
+            record.args()
+        );
+        let mut lines = self.log.0.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Keeps the log for the screen, at the level of `RUST_LOG` if it is a plain level.
+pub fn capture() -> Result<Log> {
+    let log = Log::default();
+    let level = env::var("RUST_LOG")
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(LevelFilter::Info);
+    // Asked now, while there is a single thread.
+    let tz = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    log::set_boxed_logger(Box::new(Capture {
+        log: log.clone(),
+        level,
+        tz,
+    }))?;
+    log::set_max_level(level);
+    Ok(log)
+}
+
+/// Gives the terminal back when the daemon exits, however it was asked to.
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// Takes over the terminal and draws on a thread of its own, as reading keys blocks.
+pub fn spawn(status: watch::Receiver<Status>, tx: wake::Sender, log: Log) -> Guard {
+    let terminal = ratatui::init();
+    thread::spawn(move || {
+        if let Err(e) = run(terminal, status, &tx, &log) {
+            error!("tui: {:#}", e);
+        }
+        let _ = tx.send(Wake::Shutdown);
+    });
+    Guard
+}
+
+fn draw(frame: &mut Frame, status: &Status, log: &Log, list: &mut ListState) {
+    let [head, body, tail] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(10),
+    ])
+    .areas(frame.area());
+
+    let head_text = Paragraph::new(status.line()).block(Block::bordered().title(" illust-notify "));
+    frame.render_widget(head_text, head);
+
+    let items: Vec<ListItem> = status
+        .unread
+        .iter()
+        .map(|i| ListItem::new(format!("{}  {}  #{}", i.title, i.user.name, i.id)))
+        .collect();
+    let title = format!(" unread ({}) ", status.unread.len());
+    let items = List::new(items)
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::new().reversed());
+    frame.render_stateful_widget(items, body, list);
+
+    let height = tail.height.saturating_sub(2) as usize;
+    let lines = Paragraph::new(log.lines(height).join("\n"))
+        .block(Block::bordered().title(" log "))
+        .dim();
+    frame.render_widget(lines, tail);
+}
+
+fn run(
+    mut terminal: DefaultTerminal,
+    status: watch::Receiver<Status>,
+    tx: &wake::Sender,
+    log: &Log,
+) -> Result<()> {
+    let mut list = ListState::default();
+    loop {
+        let current = status.borrow().clone();
+        // Keep the selection on the list as it shrinks.
+        let last = current.unread.len().checked_sub(1);
+        match list.selected() {
+            Some(i) if last.is_some_and(|l| i > l) => list.select(last),
+            None => list.select(last.map(|_| 0)),
+            _ => {}
+        }
+        terminal.draw(|f| draw(f, &current, log, &mut list))?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char('r') => tx.send(Wake::ManualRefresh)?,
+            KeyCode::Char('m') => tx.send(Wake::MarkRead)?,
+            KeyCode::Char('o') | KeyCode::Enter => {
+                if let Some(illust) = list.selected().and_then(|i| current.unread.get(i)) {
+                    if let Err(e) = browse::illust(illust.id, tx) {
+                        warn!("{:#}", e);
+                    }
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => list.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => list.select_previous(),
+            KeyCode::Home | KeyCode::Char('g') => list.select_first(),
+            KeyCode::End | KeyCode::Char('G') => list.select(last),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! `illust-notify update`: replaces the binary with the latest GitHub release.
+//!
+//! The release has to carry a bare binary named after the OS and architecture, e.g.
+//! `illust-notify-x86_64-linux`, with its SHA-256 either in `<asset>.sha256` or in a
+//! `SHA256SUMS` asset; releases without a checksum are refused.
+
+use std::env;
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const RELEASES: &str = "https://api.github.com/repos/karin0/illust-notify/releases/latest";
+
+#[derive(Deserialize, Debug)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// `v1.2.3` or `1.2.3` as numbers, missing or odd parts counting as 0, and whether it is a
+/// release, so that `1.2.3-rc1` orders before `1.2.3`.
+fn version(s: &str) -> (Vec<u64>, bool) {
+    let s = s.trim_start_matches('v');
+    let (numbers, pre) = s.split_once('-').map_or((s, None), |(n, p)| (n, Some(p)));
+    let numbers = numbers.split('.').map(|p| p.parse().unwrap_or(0)).collect();
+    (numbers, pre.is_none())
+}
+
+fn os_names() -> &'static [&'static str] {
+    match env::consts::OS {
+        "macos" => &["macos", "darwin", "apple"],
+        "windows" => &["windows"],
+        "android" => &["android"],
+        "linux" => &["linux"],
+        _ => &[],
+    }
+}
+
+impl Release {
+    pub fn newer(&self) -> bool {
+        version(&self.tag_name) > version(env!("CARGO_PKG_VERSION"))
+    }
+
+    fn binary(&self) -> Option<&Asset> {
+        self.assets.iter().find(|a| {
+            let name = a.name.to_lowercase();
+            name.contains(env::consts::ARCH)
+                && os_names().iter().any(|os| name.contains(os))
+                && !name.ends_with(".sha256")
+        })
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+pub struct Updater {
+    client: reqwest::Client,
+}
+
+impl Updater {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        Ok(self
+            .client
+            .get(url)
+            .header("User-Agent", env!("CARGO_PKG_NAME"))
+            .send()
+            .await?
+            .error_for_status()?)
+    }
+
+    pub async fn latest(&self) -> Result<Release> {
+        Ok(self.get(RELEASES).await?.json().await?)
+    }
+
+    /// The expected hex digest of `asset`.
+    async fn checksum(&self, release: &Release, asset: &Asset) -> Result<String> {
+        if let Some(sum) = release.asset(&format!("{}.sha256", asset.name)) {
+            let text = self.get(&sum.browser_download_url).await?.text().await?;
+            return text
+                .split_whitespace()
+                .next()
+                .map(str::to_lowercase)
+                .context("empty checksum");
+        }
+        let sums = release
+            .asset("SHA256SUMS")
+            .context("the release has no checksums")?;
+        let text = self.get(&sums.browser_download_url).await?.text().await?;
+        text.lines()
+            .filter_map(|l| l.split_once(char::is_whitespace))
+            .find(|(_, name)| name.trim().trim_start_matches('*') == asset.name)
+            .map(|(sum, _)| sum.to_lowercase())
+            .with_context(|| format!("{} is not in SHA256SUMS", asset.name))
+    }
+
+    /// Downloads the binary of `release`, verifies it and puts it in place of this one.
+    pub async fn install(&self, release: &Release) -> Result<()> {
+        let asset = release.binary().with_context(|| {
+            format!(
+                "no binary for {}-{} in {}",
+                env::consts::ARCH,
+                env::consts::OS,
+                release.tag_name
+            )
+        })?;
+        let expected = self.checksum(release, asset).await?;
+        info!("downloading {}", asset.name);
+        let data = self.get(&asset.browser_download_url).await?.bytes().await?;
+        let actual = format!("{:x}", Sha256::digest(&data));
+        if actual != expected {
+            bail!(
+                "checksum mismatch for {}: {} != {}",
+                asset.name,
+                actual,
+                expected
+            );
+        }
+
+        let exe = env::current_exe()?;
+        let new = exe.with_extension("new");
+        fs::write(&new, &data)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+        }
+        // Windows keeps the running binary open but lets it be renamed.
+        #[cfg(windows)]
+        fs::rename(&exe, exe.with_extension("old"))?;
+        fs::rename(&new, &exe).with_context(|| format!("replace {}", exe.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        assert_eq!(version("v1.2.3"), version("1.2.3"));
+        assert!(version("1.10.0") > version("1.9.9"));
+        assert!(version("1.2.3.1") > version("1.2.3"));
+        assert!(version("1.2.3") > version("1.2.3-rc1"));
+        assert!(version("1.2.3-rc1") > version("1.2.2"));
+    }
+}
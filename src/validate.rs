@@ -0,0 +1,273 @@
+//! Reads `config.json` with warnings for unknown keys and errors for values out of range.
+//!
+//! Unknown keys are only warned about, as they may belong to a newer version or to a feature
+//! this build lacks. The key names of each section come from its derived `Deserialize`, except
+//! the top level, whose `flatten` hides them; [`CONFIG_KEYS`] lists those.
+
+use std::cell::Cell;
+
+use anyhow::{bail, Result};
+use illust_notify::process::{self, ProcessConfig};
+use illust_notify::thumbs::ThumbsConfig;
+use illust_notify::TrackerConfig;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::Deserializer;
+use serde_json::Value;
+
+use crate::Config;
+
+/// The fields of [`Config`] itself, besides those of [`TrackerConfig`].
+const CONFIG_KEYS: &[&str] = &[
+    "refresh_token",
+    "delay",
+    "schedule",
+    "notify_cooldown",
+    "notify_threshold",
+    "notify_reminder",
+    "statusbar",
+    "callback_interface",
+    "callbacks",
+    "error_threshold",
+    "refresh_deadline",
+    "offline_probe",
+    "outage_delay",
+    "outage_threshold",
+    "gap_report_hours",
+    "metered_delay",
+    "delay_on_battery",
+    "delay_jitter_pct",
+    "http",
+    "matrix",
+    "termux",
+    "launcher",
+    "wallpaper",
+    "hotkey",
+    "clipboard",
+    "qr",
+    "rankings",
+    "ranking_interval",
+    "searches",
+    "search_interval",
+    "digest",
+    "event_log",
+    "pixivision",
+    "fanbox",
+    "influx",
+    "otlp",
+    "sync",
+    "rules",
+    "default_action",
+    "alert_keywords",
+    "watchdog_abort",
+    "keyring",
+    "keyring_account",
+    "dismiss_opened",
+    "notify_urls",
+];
+
+/// Hands the field names a derived `Deserialize` passes to `deserialize_struct` back.
+struct Probe<'a>(&'a Cell<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for Probe<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.set(fields);
+        Err(de::Error::custom("probed"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// The keys of a struct without `flatten`.
+fn fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    let found = Cell::new(&[][..]);
+    let _ = T::deserialize(Probe(&found));
+    found.get()
+}
+
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(cur)
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+fn check_keys(path: &str, v: &Value, known: &[&str]) {
+    let Some(map) = v.as_object() else {
+        return;
+    };
+    for key in map.keys().filter(|k| !known.contains(&k.as_str())) {
+        let close = known
+            .iter()
+            .map(|k| (distance(key, k), k))
+            .filter(|(d, _)| *d <= 2)
+            .min();
+        match close {
+            Some((_, k)) => warn!("config: unknown key {}{}, did you mean {}?", path, key, k),
+            None => warn!("config: unknown key {}{}, ignored", path, key),
+        }
+    }
+}
+
+fn check_sections(v: &Value) {
+    let sections: &[(&str, &[&str])] = &[
+        ("http", fields::<crate::server::HttpConfig>()),
+        ("matrix", fields::<crate::matrix::MatrixConfig>()),
+        ("termux", fields::<crate::termux::TermuxConfig>()),
+        ("launcher", fields::<crate::launcher::LauncherConfig>()),
+        ("statusbar", fields::<crate::statusbar::StatusbarConfig>()),
+        ("digest", fields::<crate::digest::DigestConfig>()),
+        ("event_log", fields::<crate::eventlog::EventLogConfig>()),
+        (
+            "pixivision",
+            fields::<crate::pixivision::PixivisionConfig>(),
+        ),
+        ("fanbox", fields::<crate::fanbox::FanboxConfig>()),
+        ("influx", fields::<crate::influx::InfluxConfig>()),
+        ("otlp", fields::<crate::otel::OtlpConfig>()),
+        ("sync", fields::<crate::sync::SyncConfig>()),
+        ("thumbs", fields::<ThumbsConfig>()),
+        ("process", fields::<ProcessConfig>()),
+        ("callbacks", fields::<crate::callback::CallbackConfig>()),
+        ("rankings", fields::<crate::ranking::RankingConfig>()),
+        ("searches", fields::<crate::search::SearchConfig>()),
+        ("rules", fields::<crate::rules::Rule>()),
+    ];
+    for (key, known) in sections {
+        match v.get(key) {
+            Some(Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    check_keys(&format!("{}[{}].", key, i), item, known);
+                }
+            }
+            Some(section) => check_keys(&format!("{}.", key), section, known),
+            None => {}
+        }
+    }
+}
+
+/// Values that parse but cannot work.
+fn check_ranges(config: &Config) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut at_least_one = |key: &str, v: u64| {
+        if v == 0 {
+            errors.push(format!("{} must be at least 1", key));
+        }
+    };
+    at_least_one("delay", config.delay.into());
+    at_least_one("refresh_deadline", config.refresh_deadline.into());
+    at_least_one("offline_probe", config.offline_probe.into());
+    at_least_one("outage_delay", config.outage_delay.into());
+    at_least_one("outage_threshold", config.outage_threshold.into());
+    at_least_one("ranking_interval", config.ranking_interval);
+    at_least_one("search_interval", config.search_interval);
+    at_least_one("max_pages", config.tracker.max_pages.into());
+    at_least_one("request_timeout", config.tracker.request_timeout.into());
+    at_least_one("metered_max_pages", config.tracker.metered_max_pages.into());
+    if let Some(d) = config.metered_delay {
+        at_least_one("metered_delay", d.into());
+    }
+    if let Some(d) = config.delay_on_battery {
+        at_least_one("delay_on_battery", d.into());
+    }
+    if config.delay_jitter_pct > 100 {
+        errors.push("delay_jitter_pct must be at most 100".to_owned());
+    }
+    if let Some(cap) = config.tracker.max_pages_cap {
+        if cap < config.tracker.max_pages {
+            errors.push("max_pages_cap must be at least max_pages".to_owned());
+        }
+    }
+    errors
+}
+
+/// Parses `text`, warning about unknown keys and failing on values out of range.
+pub fn parse(text: &str) -> Result<Config> {
+    // Syntax errors and missing or mistyped fields come with their line and column.
+    let config: Config = serde_json::from_str(text)?;
+    let v: Value = serde_json::from_str(text)?;
+    let mut known = CONFIG_KEYS.to_vec();
+    known.extend(fields::<TrackerConfig>());
+    check_keys("", &v, &known);
+    check_sections(&v);
+
+    if config.tracker.blur_sanity_level.is_some() && !process::CAN_BLUR {
+        warn!("config: blur_sanity_level needs the image feature, those images are left out");
+    }
+
+    let errors = check_ranges(&config);
+    if !errors.is_empty() {
+        bail!("{}", errors.join("; "));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The top-level field names of `config`, from its derived `Debug`.
+    fn debug_fields(config: &Config) -> Vec<String> {
+        format!("{:#?}", config)
+            .lines()
+            .filter_map(|l| l.strip_prefix("    "))
+            .filter(|l| !l.starts_with(' '))
+            .filter_map(|l| Some(l.split_once(": ")?.0.to_owned()))
+            .filter(|k| k != "tracker")
+            .collect()
+    }
+
+    #[test]
+    fn config_keys_match_config() {
+        let config = parse(r#"{"refresh_token": "x"}"#).unwrap();
+        let fields = debug_fields(&config);
+        for key in &fields {
+            assert!(
+                CONFIG_KEYS.contains(&key.as_str()),
+                "{} is missing from CONFIG_KEYS",
+                key
+            );
+        }
+        // Fields of features left out of this build are not in `Config`.
+        if cfg!(all(
+            feature = "wallpaper",
+            feature = "hotkey",
+            feature = "clipboard",
+            feature = "qr"
+        )) {
+            for key in CONFIG_KEYS {
+                assert!(
+                    fields.iter().any(|k| k == key),
+                    "{} is not a field of Config",
+                    key
+                );
+            }
+        }
+    }
+}
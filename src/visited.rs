@@ -0,0 +1,77 @@
+//! The set of illust ids counted as unread, kept as a roaring bitmap.
+//!
+//! State dumps embed the bitmap as a base64 string, which is far smaller than an id array for
+//! long-running instances. Id arrays written by older versions are still accepted.
+//!
+//! Use as `#[serde(with = "visited")]`.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use pixiv::model::IllustId;
+use roaring::RoaringTreemap;
+use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+#[derive(Debug, Clone, Default)]
+pub struct Visited(RoaringTreemap);
+
+impl Visited {
+    pub fn contains(&self, id: IllustId) -> bool {
+        self.0.contains(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn insert(&mut self, id: IllustId) -> bool {
+        self.0.insert(id)
+    }
+
+    pub fn remove(&mut self, id: IllustId) -> bool {
+        self.0.remove(id)
+    }
+
+    /// Forgets the ids up to and including `id`.
+    pub fn remove_up_to(&mut self, id: IllustId) {
+        self.0.remove_range(..=id);
+    }
+}
+
+impl Extend<IllustId> for Visited {
+    fn extend<I: IntoIterator<Item = IllustId>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl FromIterator<IllustId> for Visited {
+    fn from_iter<I: IntoIterator<Item = IllustId>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+pub fn serialize<S: Serializer>(vis: &Visited, s: S) -> Result<S::Ok, S::Error> {
+    let mut buf = Vec::with_capacity(vis.0.serialized_size());
+    vis.0.serialize_into(&mut buf).map_err(ser::Error::custom)?;
+    s.serialize_str(&STANDARD.encode(buf))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Bitmap(String),
+    Ids(Vec<IllustId>),
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Visited, D::Error> {
+    Ok(match Repr::deserialize(d)? {
+        Repr::Bitmap(s) => {
+            let buf = STANDARD.decode(s).map_err(de::Error::custom)?;
+            Visited(RoaringTreemap::deserialize_from(&buf[..]).map_err(de::Error::custom)?)
+        }
+        Repr::Ids(ids) => ids.into_iter().collect(),
+    })
+}
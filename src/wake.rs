@@ -0,0 +1,327 @@
+//! Everything that wakes the main loop, funneled through one channel.
+//!
+//! Each source gets a clone of the [`Sender`]; the main loop is the only receiver.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use inotify::{EventMask, Inotify, WatchMask};
+use pixiv::model::IllustId;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration, Instant};
+
+const RESUME_CHECK: Duration = Duration::from_secs(10);
+/// Sleeps shorter than this go unnoticed.
+const RESUME_GAP: Duration = Duration::from_secs(30);
+const SNOOZE: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub enum Wake {
+    /// The poll delay elapsed.
+    Tick,
+    /// Refresh now, scanning fully and allowing an alert within the cooldown.
+    ManualRefresh,
+    Shutdown,
+    /// Re-read `config.json`.
+    ConfigReload,
+    /// Suppress notifications until the given instant, or resume them with `None`.
+    Snooze(Option<Instant>),
+    /// Switch the metered mode on or off, or toggle it with `None`.
+    Metered(Option<bool>),
+    /// Move the anchor to the newest illust, in the `mark_read` and `hybrid` anchor modes.
+    MarkRead,
+    /// The system woke up from sleep, the delay may have been waited out in the meantime.
+    Resumed,
+    /// Announce the current count as a `status` event.
+    Status,
+    /// An unread illust was opened in the browser, see `dismiss_opened`.
+    Opened(IllustId),
+}
+
+pub type Sender = mpsc::UnboundedSender<Wake>;
+pub type Receiver = mpsc::UnboundedReceiver<Wake>;
+
+pub fn channel() -> (Sender, Receiver) {
+    mpsc::unbounded_channel()
+}
+
+/// `30m`, `2h`, `90s`, `1d`, or minutes without a unit.
+fn duration(s: &str) -> Result<Duration> {
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "m"),
+    };
+    let n = n
+        .parse::<u64>()
+        .with_context(|| format!("invalid duration {:?}", s))?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => bail!("invalid duration {:?}", s),
+    };
+    let secs = n
+        .checked_mul(secs)
+        .with_context(|| format!("duration {:?} too long", s))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// `Instant::now() + d`, which would panic past what the clock can represent.
+fn after(d: Duration) -> Result<Instant> {
+    Instant::now()
+        .checked_add(d)
+        .with_context(|| format!("{}s is too far ahead", d.as_secs()))
+}
+
+/// A line of the notify file: `refresh`, `mark-read`, `snooze [30m]`, `metered [on|off]`,
+/// `reload`, `status` or `quit`.
+fn command(line: &str) -> Result<Wake> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    Ok(match words[..] {
+        ["refresh"] => Wake::ManualRefresh,
+        ["mark-read" | "markread"] => Wake::MarkRead,
+        ["snooze"] => Wake::Snooze(Some(after(SNOOZE)?)),
+        ["snooze", d] => match duration(d)? {
+            d if d.is_zero() => Wake::Snooze(None),
+            d => Wake::Snooze(Some(after(d)?)),
+        },
+        ["metered"] => Wake::Metered(None),
+        ["metered", "on"] => Wake::Metered(Some(true)),
+        ["metered", "off"] => Wake::Metered(Some(false)),
+        ["reload"] => Wake::ConfigReload,
+        ["status"] => Wake::Status,
+        ["quit"] => Wake::Shutdown,
+        _ => bail!("unknown command {:?}", line.trim()),
+    })
+}
+
+/// Reads what was written to `path` and empties it, so touching it later is a plain refresh.
+fn drain(path: &str, own: &mut u32) -> io::Result<String> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    // Closing it is another write.
+    *own += 1;
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    file.set_len(0)?;
+    Ok(text)
+}
+
+fn commands(path: &str, own: &mut u32) -> Vec<Wake> {
+    match drain(path, own) {
+        Ok(text) if text.trim().is_empty() => vec![Wake::ManualRefresh],
+        Ok(text) => text
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .filter_map(|l| command(l).inspect_err(|e| warn!("{}: {:#}", path, e)).ok())
+            .collect(),
+        Err(e) => {
+            error!("{}: {}", path, e);
+            vec![Wake::ManualRefresh]
+        }
+    }
+}
+
+/// Watches `path` for commands, one per line, e.g. `echo snooze 30m > notify`.
+///
+/// Opening it without writing, e.g. from a notification action, or touching it requests a
+/// refresh as before.
+pub fn inotify(path: &str, tx: Sender) -> Result<()> {
+    let inotify = Inotify::init()?;
+    inotify
+        .watches()
+        .add(path, WatchMask::CLOSE_WRITE | WatchMask::CLOSE_NOWRITE)?;
+    let mut events = inotify.into_event_stream([0; 128])?;
+    let path = path.to_owned();
+    tokio::spawn(async move {
+        // Writes of our own from emptying the file.
+        let mut own = 0;
+        while let Some(r) = events.next().await {
+            let e = match r {
+                Ok(e) => e,
+                Err(e) => {
+                    error!("inotify: {}", e);
+                    break;
+                }
+            };
+            let wakes = if !e.mask.contains(EventMask::CLOSE_WRITE) {
+                vec![Wake::ManualRefresh]
+            } else if own > 0 {
+                own -= 1;
+                continue;
+            } else {
+                commands(&path, &mut own)
+            };
+            info!("inotify: {:?}: {:?}", e.mask, wakes);
+            for w in wakes {
+                if tx.send(w).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The command named by a file created in the triggers directory, with its content as the
+/// argument, e.g. `echo 30m > triggers/snooze`.
+fn trigger(path: &Path) -> Result<Wake> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("invalid name")?;
+    let arg = fs::read_to_string(path).unwrap_or_default();
+    command(&format!("{} {}", name, arg.trim()))
+}
+
+/// Runs the command of each file created in `dir`, then deletes it; `refresh`, `markread` or
+/// `quit` are enough for a click handler to `touch`.
+pub fn triggers(dir: &str, tx: Sender) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    // Left over from a previous run, a stale `quit` must not end this one.
+    for entry in fs::read_dir(dir)?.flatten() {
+        debug!("removing stale trigger {}", entry.path().display());
+        let _ = fs::remove_file(entry.path());
+    }
+    let inotify = Inotify::init()?;
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    let mut events = inotify.into_event_stream([0; 1024])?;
+    let dir = Path::new(dir).to_owned();
+    tokio::spawn(async move {
+        while let Some(r) = events.next().await {
+            let e = match r {
+                Ok(e) => e,
+                Err(e) => {
+                    error!("inotify: {}", e);
+                    break;
+                }
+            };
+            // Editors and atomic writers leave dotfiles around.
+            let Some(name) = e.name.filter(|n| !n.to_string_lossy().starts_with('.')) else {
+                continue;
+            };
+            let path = dir.join(name);
+            let r = trigger(&path);
+            if let Err(e) = fs::remove_file(&path) {
+                debug!("{}: {}", path.display(), e);
+            }
+            match r {
+                Ok(w) => {
+                    info!("trigger {}: {:?}", path.display(), w);
+                    if tx.send(w).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("trigger {}: {:#}", path.display(), e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Notices system sleep by the wall clock running ahead of the monotonic one, which stands
+/// still while suspended on Linux and macOS.
+///
+/// This needs no D-Bus or power event subscription, at the cost of also taking a clock set
+/// forward for a resume, which costs no more than an early refresh.
+pub fn resume(tx: Sender) {
+    tokio::spawn(async move {
+        let mut mono = Instant::now();
+        let mut wall = SystemTime::now();
+        loop {
+            sleep(RESUME_CHECK).await;
+            let (m, w) = (Instant::now(), SystemTime::now());
+            let slept = w
+                .duration_since(wall)
+                .unwrap_or_default()
+                .saturating_sub(m - mono);
+            (mono, wall) = (m, w);
+            if slept >= RESUME_GAP {
+                info!("resumed after about {}s asleep", slept.as_secs());
+                if tx.send(Wake::Resumed).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Shuts down on SIGINT and SIGTERM, and re-reads `config.json` on SIGHUP.
+pub fn signals(tx: Sender) -> Result<()> {
+    let shutdown = tx.clone();
+    ctrlc::set_handler(move || {
+        warn!("shutting down");
+        let _ = shutdown.send(Wake::Shutdown);
+    })?;
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut term = signal(SignalKind::terminate())?;
+        let mut hup = signal(SignalKind::hangup())?;
+        tokio::spawn(async move {
+            loop {
+                let wake = tokio::select! {
+                    _ = term.recv() => {
+                        warn!("shutting down");
+                        Wake::Shutdown
+                    }
+                    _ = hup.recv() => {
+                        info!("SIGHUP, reloading the config");
+                        Wake::ConfigReload
+                    }
+                };
+                if tx.send(wake).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(duration("1d").unwrap(), Duration::from_secs(86400));
+        assert_eq!(duration("45").unwrap(), Duration::from_secs(2700));
+        assert!(duration("").is_err());
+        assert!(duration("m").is_err());
+        assert!(duration("5w").is_err());
+        assert!(duration(&format!("{}d", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(command("refresh\n"), Ok(Wake::ManualRefresh)));
+        assert!(matches!(command("markread"), Ok(Wake::MarkRead)));
+        assert!(matches!(command("snooze"), Ok(Wake::Snooze(Some(_)))));
+        assert!(matches!(command("snooze 0"), Ok(Wake::Snooze(None))));
+        assert!(matches!(command(" snooze  2h "), Ok(Wake::Snooze(Some(_)))));
+        assert!(matches!(command("metered"), Ok(Wake::Metered(None))));
+        assert!(matches!(
+            command("metered off"),
+            Ok(Wake::Metered(Some(false)))
+        ));
+        assert!(command("metered maybe").is_err());
+        assert!(command("").is_err());
+    }
+
+    #[test]
+    fn snooze_overflow() {
+        assert!(command(&format!("snooze {}d", u64::MAX)).is_err());
+        assert!(command(&format!("snooze {}s", u64::MAX)).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Sets the desktop background to the bookmarked anchor whenever it changes.
+
+use std::{env, fs};
+
+use anyhow::{Context, Result};
+use illust_notify::{ImageSize, Tracker};
+use serde::Deserialize;
+
+fn default_size() -> ImageSize {
+    ImageSize::Large
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Center,
+    #[default]
+    Crop,
+    Fit,
+    Span,
+    Stretch,
+    Tile,
+}
+
+impl From<Mode> for wallpaper::Mode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Center => Self::Center,
+            Mode::Crop => Self::Crop,
+            Mode::Fit => Self::Fit,
+            Mode::Span => Self::Span,
+            Mode::Stretch => Self::Stretch,
+            Mode::Tile => Self::Tile,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WallpaperConfig {
+    #[serde(default = "default_size")]
+    size: ImageSize,
+    #[serde(default)]
+    mode: Mode,
+    /// Only illusts carrying one of these tags are used, any illust if empty.
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl WallpaperConfig {
+    /// Downloads the anchor and sets it as the background, if it is a bookmark and may be shown.
+    pub async fn apply(&self, app: &Tracker) -> Result<()> {
+        let Some(illust) = app
+            .anchor
+            .as_ref()
+            .filter(|i| i.is_bookmarked && app.shown(i))
+        else {
+            return Ok(());
+        };
+        if !self.tags.is_empty() && !illust.tags.iter().any(|t| self.tags.contains(&t.name)) {
+            debug!("wallpaper: {} has no allowed tag", illust.id);
+            return Ok(());
+        }
+        let url = illust.image_url(self.size);
+        let ext = if url.ends_with(".png") { "png" } else { "jpg" };
+        // Some desktops only take absolute paths.
+        let path = env::current_dir()?.join(format!("wallpaper.{}", ext));
+
+        fs::write(&path, app.download(url).await?)?;
+
+        let path = path.to_str().context("non-UTF-8 state directory")?;
+        wallpaper::set_from_path(path).map_err(|e| anyhow::Error::msg(e.to_string()))?;
+        wallpaper::set_mode(self.mode.into()).map_err(|e| anyhow::Error::msg(e.to_string()))?;
+        info!("wallpaper set to {}: {}", illust.id, illust.title);
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! Notices when the main loop stops making progress, e.g. a hang in the HTTP stack that
+//! outlives every timeout.
+//!
+//! It runs on its own thread since a stuck loop may be blocking the runtime, so it can only
+//! reach the callbacks subscribed to `error`, not the other backends.
+
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::callback::{CallbackConfig, Event, Payload};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Beat {
+    at: Instant,
+    allowed: Duration,
+    phase: &'static str,
+    reported: bool,
+}
+
+#[derive(Clone)]
+pub struct Watchdog(Arc<Mutex<Beat>>);
+
+impl Watchdog {
+    /// Starts watching, allowing `allowed` until the first [`Watchdog::beat`].
+    pub fn spawn(allowed: Duration, callbacks: Vec<CallbackConfig>, abort: bool) -> Self {
+        let dog = Self(Arc::new(Mutex::new(Beat {
+            at: Instant::now(),
+            allowed,
+            phase: "startup",
+            reported: false,
+        })));
+        let beat = dog.0.clone();
+        let callbacks: Vec<_> = callbacks
+            .into_iter()
+            .filter(|cb| cb.events.contains(&Event::Error))
+            .collect();
+        thread::spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+            let msg = {
+                let Ok(mut beat) = beat.lock() else {
+                    return;
+                };
+                let elapsed = beat.at.elapsed();
+                if beat.reported || elapsed <= beat.allowed {
+                    continue;
+                }
+                beat.reported = true;
+                format!(
+                    "main loop stuck in {} for {}s, {}s allowed",
+                    beat.phase,
+                    elapsed.as_secs(),
+                    beat.allowed.as_secs()
+                )
+            };
+            error!("watchdog: {}", msg);
+            let payload = Payload::bare(Event::Error, msg);
+            for cb in &callbacks {
+                if let Err(e) = cb.run(&payload) {
+                    error!("watchdog: {}: {:#}", cb.path, e);
+                }
+            }
+            if abort {
+                error!("watchdog: aborting");
+                process::abort();
+            }
+        });
+        dog
+    }
+
+    /// Records progress, expecting the next one within `allowed`.
+    pub fn beat(&self, phase: &'static str, allowed: Duration) {
+        if let Ok(mut beat) = self.0.lock() {
+            if beat.reported {
+                info!("watchdog: main loop resumed");
+            }
+            *beat = Beat {
+                at: Instant::now(),
+                allowed,
+                phase,
+                reported: false,
+            };
+        }
+    }
+
+    /// Names what the loop is doing for the report, without resetting the clock.
+    pub fn phase(&self, phase: &'static str) {
+        if let Ok(mut beat) = self.0.lock() {
+            beat.phase = phase;
+        }
+    }
+}
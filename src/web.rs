@@ -0,0 +1,128 @@
+//! The follow feed of the pixiv website, used when the AppAPI refresh token stops working.
+//!
+//! Needs the `PHPSESSID` cookie of a browser login as `web_session`. The website pages hold
+//! fewer details than the AppAPI, e.g. no captions, but enough to find the anchor and count.
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::{Illust, ImageUrls, Tag, User};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct WebIllust {
+    id: String,
+    title: String,
+    user_id: String,
+    user_name: String,
+    create_date: String,
+    /// Present when bookmarked.
+    bookmark_data: Option<serde_json::Value>,
+    url: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    x_restrict: u32,
+    #[serde(default)]
+    sl: u32,
+}
+
+impl From<WebIllust> for Illust {
+    fn from(w: WebIllust) -> Self {
+        Illust {
+            id: w.id.parse().unwrap_or_default(),
+            title: w.title,
+            create_date: w.create_date,
+            is_bookmarked: w.bookmark_data.is_some(),
+            image_urls: ImageUrls {
+                square_medium: w.url,
+                medium: String::new(),
+                large: String::new(),
+            },
+            user: User {
+                id: w.user_id.parse().unwrap_or_default(),
+                name: w.user_name,
+                is_followed: true,
+            },
+            caption: String::new(),
+            tags: w
+                .tags
+                .into_iter()
+                .map(|name| Tag {
+                    name,
+                    translated_name: None,
+                })
+                .collect(),
+            meta_single_page: Default::default(),
+            meta_pages: Vec::new(),
+            sanity_level: w.sl,
+            x_restrict: w.x_restrict,
+            total_bookmarks: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Thumbnails {
+    illust: Vec<WebIllust>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    #[serde(default)]
+    is_last_page: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Body {
+    page: PageInfo,
+    thumbnails: Thumbnails,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    error: bool,
+    #[serde(default)]
+    message: String,
+    body: Option<Body>,
+}
+
+pub struct WebClient {
+    client: reqwest::Client,
+}
+
+impl Default for WebClient {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl WebClient {
+    /// Page `p` of the follow feed, newest first, and whether it is the last one.
+    pub async fn follow(&self, session: &str, p: u32) -> Result<(Vec<Illust>, bool)> {
+        let url = format!(
+            "https://www.pixiv.net/ajax/follow_latest/illust?p={}&mode=all&lang=en",
+            p
+        );
+        let r: Response = self
+            .client
+            .get(&url)
+            .header("Cookie", format!("PHPSESSID={}", session))
+            .header("Referer", "https://www.pixiv.net/")
+            .header("User-Agent", "Mozilla/5.0")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let Some(body) = r.body.filter(|_| !r.error) else {
+            bail!("web: {}", r.message);
+        };
+        let mut illusts: Vec<Illust> = body.thumbnails.illust.into_iter().map(Into::into).collect();
+        illusts.sort_unstable_by_key(|i| std::cmp::Reverse(i.id));
+        Ok((illusts, body.page.is_last_page))
+    }
+}
@@ -0,0 +1,187 @@
+//! `GET /ws`: the `/events` stream as WebSocket text frames, for dashboards, OBS overlays and
+//! Stream Deck plugins that would rather not poll.
+//!
+//! Each message is `{"event":"count","data":{...}}` with the status, or
+//! `{"event":"error","data":"..."}`. Only what a push-only server needs of RFC 6455 is here:
+//! the handshake, unmasked frames out, and pings and closes in.
+
+use anyhow::{bail, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+
+use crate::server::{Pushed, Status};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const KEEPALIVE: Duration = Duration::from_secs(30);
+/// Clients only send control frames here, which are small.
+const MAX_FRAME: u64 = 4096;
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+/// The `Sec-WebSocket-Accept` answering a `Sec-WebSocket-Key`.
+fn accept(key: &str) -> String {
+    STANDARD.encode(Sha1::digest(format!("{}{}", key.trim(), GUID)))
+}
+
+/// A final, unmasked frame, as servers send them.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut f = vec![0x80 | opcode];
+    match payload.len() {
+        n if n < 126 => f.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            f.push(126);
+            f.extend((n as u16).to_be_bytes());
+        }
+        n => {
+            f.push(127);
+            f.extend((n as u64).to_be_bytes());
+        }
+    }
+    f.extend_from_slice(payload);
+    f
+}
+
+/// Reads a client frame, returning its opcode and unmasked payload.
+async fn read_frame<R: AsyncRead + Unpin>(r: &mut R) -> Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    r.read_exact(&mut head).await?;
+    let opcode = head[0] & 0x0f;
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut b = [0; 2];
+            r.read_exact(&mut b).await?;
+            u16::from_be_bytes(b).into()
+        }
+        127 => {
+            let mut b = [0; 8];
+            r.read_exact(&mut b).await?;
+            u64::from_be_bytes(b)
+        }
+        n => n.into(),
+    };
+    if head[1] & 0x80 == 0 {
+        bail!("unmasked client frame");
+    }
+    if len > MAX_FRAME {
+        bail!("frame of {} bytes", len);
+    }
+    let mut mask = [0; 4];
+    r.read_exact(&mut mask).await?;
+    let mut payload = vec![0; len as usize];
+    r.read_exact(&mut payload).await?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+/// Upgrades the connection and pushes the status until either side closes it.
+pub async fn serve(
+    stream: TcpStream,
+    key: &str,
+    mut status: watch::Receiver<Status>,
+) -> Result<()> {
+    let (mut rd, mut wr) = stream.into_split();
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept(key)
+    );
+    wr.write_all(head.as_bytes()).await?;
+
+    // Reading a frame is not cancel safe, so the reader runs apart and hands over the replies.
+    let (tx, mut replies) = mpsc::channel(4);
+    tokio::spawn(async move {
+        loop {
+            let reply = match read_frame(&mut rd).await {
+                Ok((PING, p)) => (PONG, p),
+                Ok((CLOSE, p)) => (CLOSE, p),
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("ws: {:#}", e);
+                    break;
+                }
+            };
+            let close = reply.0 == CLOSE;
+            if tx.send(reply).await.is_err() || close {
+                break;
+            }
+        }
+    });
+
+    let mut pushed = Pushed::default();
+    loop {
+        let current = status.borrow_and_update().clone();
+        for (event, data) in pushed.changes(&current)? {
+            let msg = format!("{{\"event\":\"{}\",\"data\":{}}}", event, data);
+            wr.write_all(&frame(TEXT, msg.as_bytes())).await?;
+        }
+        tokio::select! {
+            r = status.changed() => if r.is_err() {
+                // Shutting down.
+                wr.write_all(&frame(CLOSE, b"")).await?;
+                return Ok(());
+            },
+            reply = replies.recv() => match reply {
+                Some((opcode, p)) => {
+                    wr.write_all(&frame(opcode, &p)).await?;
+                    if opcode == CLOSE {
+                        return Ok(());
+                    }
+                }
+                None => return Ok(()),
+            },
+            _ = sleep(KEEPALIVE) => wr.write_all(&frame(PING, b"")).await?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts() {
+        // The example of RFC 6455, section 1.3.
+        assert_eq!(
+            accept("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(frame(TEXT, b"hi"), [0x81, 2, b'h', b'i']);
+        assert_eq!(frame(CLOSE, b""), [0x88, 0]);
+        assert_eq!(frame(TEXT, &[0; 126])[..4], [0x81, 126, 0, 126]);
+        let long = frame(TEXT, &[0; 65536]);
+        assert_eq!(long[..10], [0x81, 127, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(long.len(), 10 + 65536);
+    }
+
+    #[tokio::test]
+    async fn reads_masked() {
+        let mask = [1, 2, 3, 4];
+        let mut f = vec![0x89, 0x80 | 3];
+        f.extend(mask);
+        f.extend(b"abc".iter().zip(mask).map(|(b, m)| b ^ m));
+        assert_eq!(
+            read_frame(&mut &f[..]).await.unwrap(),
+            (PING, b"abc".to_vec())
+        );
+
+        // Clients have to mask.
+        assert!(read_frame(&mut &[0x89, 0][..]).await.is_err());
+        let mut big = vec![0x82, 0x80 | 127];
+        big.extend((MAX_FRAME + 1).to_be_bytes());
+        assert!(read_frame(&mut &big[..]).await.is_err());
+    }
+}